@@ -0,0 +1,46 @@
+// Times the blocked matrix product against the naive one, run with `cargo bench --bench matmul [-- SIZES..]`
+use std::time::{Duration, Instant};
+
+use quantum_stuff::complex::*;
+use quantum_stuff::dynamic::*;
+
+// The naive product takes minutes past this size
+const NAIVE_MAX: usize = 1024;
+
+fn matrix(n: usize, seed: usize) -> Matrix<C64> {
+    let entry = |k: usize| C64::new(((k * 7919 + seed) % 101) as f64 / 101.0, ((k * 104729 + seed) % 97) as f64 / 97.0);
+    Matrix::from_rows((0..n).map(|r| Vector::from_iter((0..n).map(|c| entry(r * n + c)), Some(n))), Some(n)).unwrap()
+}
+
+// Straightforward row times column product, the baseline for the blocked one
+fn mul_naive(a: &Matrix<C64>, b: &Matrix<C64>) -> Matrix<C64> {
+    let rows = a.row_iter().map(|row| {
+        Vector::from_iter(b.col_iter().map(|col| row.iter().zip(col.iter()).fold(C64::ZERO, |acc, (&x, &y)| acc + x * y)), Some(b.dim().1))
+    });
+    Matrix::from_rows(rows, Some(a.dim().0)).unwrap()
+}
+
+fn time(f: impl FnOnce() -> Matrix<C64>) -> (Duration, Matrix<C64>) {
+    let start = Instant::now();
+    let result = f();
+    (start.elapsed(), result)
+}
+
+fn main() {
+    let sizes: Vec<usize> = std::env::args().skip(1).filter_map(|arg| arg.parse().ok()).collect();
+    let sizes = if sizes.is_empty() { vec![256, 512, 1024, 2048, 4096] } else { sizes };
+
+    println!("{:>6} {:>12} {:>12} {:>8}", "n", "blocked", "naive", "speedup");
+    for n in sizes {
+        let (a, b) = (matrix(n, 1), matrix(n, 2));
+        let (blocked, product) = time(|| (&a * &b).unwrap());
+
+        if n <= NAIVE_MAX {
+            let (naive, expected) = time(|| mul_naive(&a, &b));
+            assert!(product.fuzzy_equals(&expected));
+            println!("{n:>6} {:>12.3?} {:>12.3?} {:>7.1}x", blocked, naive, naive.as_secs_f64() / blocked.as_secs_f64());
+        } else {
+            println!("{n:>6} {:>12.3?} {:>12} {:>8}", blocked, "-", "-");
+        }
+    }
+}
@@ -16,13 +16,13 @@ pub fn deutsch(f: impl Fn(usize) -> usize) -> FType {
     let mut output = State::from_qubit(true);
     
     let H = Gate::hadamard();
-    input.apply(&H);
-    output.apply(&H);
+    input.apply(&H).unwrap();
+    output.apply(&H).unwrap();
 
     let mut state = input.tensor_product(output);    
-    state.apply(&U);
+    state.apply(&U).unwrap();
 
-    state.apply_partial(0..1, &H);
+    state.apply_partial(0..1, &H).unwrap();
     
     match state.measure_partial(0..1).0 {
         0 => FType::Constant,
@@ -11,19 +11,19 @@ pub enum FType{
 // Assumes f is EITHER Constant or Balanced.
 pub fn deutsch_josza(n: usize, f: impl Fn(usize) -> usize) -> FType {
     let U = Gate::create_oracle(n, 1, f);
-    let H_N = vec![Gate::hadamard(); n].into_iter().reduce(|acc, cur| acc.tensor_product(&cur)).unwrap();
+    let H_N = Kronecker::power(Gate::hadamard().get(), n).unwrap();
 
     let mut input = vec![State::from_qubit(false);n].into_iter().reduce(|acc, cur| acc.tensor_product(cur)).unwrap();
     let mut output = State::from_qubit(true);
     
-    input.apply(&H_N);
-    output.apply(&Gate::hadamard());
+    input.apply_kronecker(&H_N).unwrap();
+    output.apply(&Gate::hadamard()).unwrap();
 
     let mut state = input.tensor_product(output);    
-    state.apply(&U);
+    state.apply(&U).unwrap();
 
 
-    state.apply_partial(0..n, &H_N);
+    state.apply_kronecker(&H_N.tensor_product(&Kronecker::identity(2))).unwrap();
     
     let res = state.measure_partial(0..n).0;
     match res {
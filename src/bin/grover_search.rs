@@ -11,7 +11,7 @@ pub fn grover_search(n: usize, f: impl Fn(usize) -> usize, loops_hint: Option<us
 
     //Gates
     let u_f = Gate::create_oracle(n, 1, f);
-    let h_n = vec![Gate::hadamard();n].into_iter().reduce(|acc, cur| acc.tensor_product(&cur)).unwrap();
+    let h_n = Kronecker::power(Gate::hadamard().get(), n).unwrap();
     let h = Gate::hadamard();
     let inversion_about_mean = {
         let entry = C64::new(1.0/(size as f64),0.0);
@@ -30,15 +30,15 @@ pub fn grover_search(n: usize, f: impl Fn(usize) -> usize, loops_hint: Option<us
     for _ in 0..trials {
         let mut initial = State::from_qubits(vec![false; n].into_iter());
 
-        initial.apply(&h_n);
+        initial.apply_kronecker(&h_n).unwrap();
 
         let mut output = State::from_qubit(true);
-        output.apply(&h);
+        output.apply(&h).unwrap();
         let mut state = initial.tensor_product(output);
         for _ in 0..loops {
             // Phase Inversion / Function Evaluation
-            state.apply(&u_f);
-            state.apply_partial(0..n, &inversion_about_mean);
+            state.apply(&u_f).unwrap();
+            state.apply_partial(0..n, &inversion_about_mean).unwrap();
         }
         let res = state.measure_partial(0..n).0;
 
@@ -0,0 +1,192 @@
+use std::io::{self, BufRead, Write};
+
+use quantum_stuff::circuit::Outcome;
+use quantum_stuff::complex::*;
+use quantum_stuff::emulator::emulator::*;
+use quantum_stuff::emulator::lexer::*;
+
+const HELP: &str = "Enter emulator instructions one line at a time, DEFINE / REPEAT blocks run once their END is entered.
+
+Commands:
+  :state R   amplitudes of the state register R lives in, in ket notation
+  :probs R   probability of each basis state of register R
+  :regs      list registers
+  :ops       list operators
+  :reset     drop all registers, operators and subroutines
+  :help      show this message
+  :quit      exit";
+
+// Amplitudes below this modulus are not printed by :state
+const AMPLITUDE_CUTOFF: f64 = 1e-9;
+
+#[derive(Default)]
+struct Repl {
+    emulator: Emulator,
+    // Lines of a DEFINE / REPEAT block which has not seen its END yet
+    pending: String,
+    depth: isize
+}
+
+impl Repl {
+    fn prompt(&self) -> &'static str {
+        if self.depth > 0 { "....> " } else { "qasm> " }
+    }
+
+    // Handles one line of input, returning what should be printed and whether to keep going.
+    fn handle_line(&mut self, line: &str) -> (String, bool) {
+        let trimmed = line.trim();
+        if self.depth == 0 {
+            if let Some(command) = trimmed.strip_prefix(':') {
+                return self.command(command);
+            }
+        }
+
+        let tokens = match scan(&mut trimmed.as_bytes()) {
+            Ok(tokens) => tokens,
+            Err(err) => { return (format!("error: {err}"), true); }
+        };
+
+        self.depth += match tokens.first().map(|token| &token.ty) {
+            Some(TokenType::Define | TokenType::Repeat) => 1,
+            Some(TokenType::End) => -1,
+            _ => 0
+        };
+        self.pending.push_str(trimmed);
+        self.pending.push('\n');
+
+        if self.depth > 0 {
+            return (String::new(), true);
+        }
+
+        self.depth = 0;
+        let source = std::mem::take(&mut self.pending);
+        let tokens = match scan(&mut source.as_bytes()) {
+            Ok(tokens) => tokens,
+            Err(err) => { return (format!("error: {err}"), true); }
+        };
+
+        match self.emulator.execute_outcomes(&tokens) {
+            Ok(outcomes) => (outcomes.iter().map(|outcome| format!("=> {}", format_outcome(outcome))).collect::<Vec<_>>().join("\n"), true),
+            Err(err) => (format!("error: {err}"), true)
+        }
+    }
+
+    fn command(&mut self, command: &str) -> (String, bool) {
+        let mut words = command.split_whitespace();
+        let output = match (words.next(), words.next()) {
+            (Some("state"), Some(name)) => match self.emulator.register_state(name) {
+                Some((state, interval)) => {
+                    let num_qubits = state.num_qubits();
+                    let mut out = format!("{name} spans qubits {}..{} of a {num_qubits} qubit state\n", interval.start, interval.end);
+                    for (k, amplitude) in state.get().iter().enumerate() {
+                        if amplitude.modulus() > AMPLITUDE_CUTOFF {
+                            out += &format!("  ({amplitude}) {}\n", ket(k, num_qubits));
+                        }
+                    }
+                    out.trim_end().to_owned()
+                },
+                None => format!("error: register {name} does not exist")
+            },
+            (Some("probs"), Some(name)) => match self.emulator.register_probabilities(name) {
+                Some(probabilities) => {
+                    let num_qubits = probabilities.len().ilog2() as usize;
+                    probabilities.iter().enumerate().map(|(k, prob)| {
+                        format!("  {} {:6.2}%", ket(k, num_qubits), prob * 100.0)
+                    }).collect::<Vec<_>>().join("\n")
+                },
+                None => format!("error: register {name} does not exist")
+            },
+            (Some("regs"), None) => self.emulator.registers().iter().map(|(name, interval)| {
+                format!("  {name}: qubits {}..{}", interval.start, interval.end)
+            }).collect::<Vec<_>>().join("\n"),
+            (Some("ops"), None) => self.emulator.operators().iter().map(|(name, dim)| {
+                format!("  {name}: {dim}x{dim}")
+            }).collect::<Vec<_>>().join("\n"),
+            (Some("reset"), None) => {
+                self.emulator.reset();
+                "Emulator reset.".to_owned()
+            },
+            (Some("help"), None) => HELP.to_owned(),
+            (Some("quit") | Some("q"), None) => { return (String::new(), false); },
+            _ => format!("error: unknown command :{command}, try :help")
+        };
+        (output, true)
+    }
+}
+
+// A measurement result as is, a PEEK as the basis states it could be measured in
+fn format_outcome(outcome: &Outcome) -> String {
+    match outcome {
+        Outcome::Measured(result) => result.to_string(),
+        Outcome::Peeked { probabilities, .. } => {
+            let num_qubits = probabilities.len().ilog2() as usize;
+            probabilities.iter().enumerate()
+                .filter(|(_, prob)| **prob > AMPLITUDE_CUTOFF)
+                .map(|(k, prob)| format!("{} {:.2}%", ket(k, num_qubits), prob * 100.0))
+                .collect::<Vec<_>>().join(", ")
+        }
+    }
+}
+
+fn ket(k: usize, num_qubits: usize) -> String {
+    format!("|{k:0num_qubits$b}⟩")
+}
+
+fn main() -> io::Result<()> {
+    let mut repl = Repl::default();
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+
+    println!("Quantum assembly REPL, :help for commands.");
+    loop {
+        stdout.write_all(repl.prompt().as_bytes())?;
+        stdout.flush()?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            break;
+        }
+
+        let (output, keep_going) = repl.handle_line(&line);
+        if !output.is_empty() {
+            println!("{output}");
+        }
+        if !keep_going {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session() {
+        let mut repl = Repl::default();
+        assert_eq!(repl.handle_line("INITIALIZE R 1\n").0, "");
+        assert_eq!(repl.handle_line("APPLY H R").0, "");
+        assert_eq!(repl.handle_line(":probs R").0, "  |0⟩  50.00%\n  |1⟩  50.00%");
+        assert_eq!(repl.handle_line("PEEK R").0, "=> |0⟩ 50.00%, |1⟩ 50.00%");
+        assert!(repl.handle_line(":state R").0.contains("|1⟩"));
+        assert!(repl.handle_line(":state S").0.starts_with("error"));
+        assert!(repl.handle_line("APPLY X R").0.starts_with("error: Operator does not exist"));
+
+        repl.handle_line(":reset");
+        assert_eq!(repl.handle_line(":regs").0, "");
+        assert!(!repl.handle_line(":quit").1);
+    }
+
+    #[test]
+    fn test_blocks() {
+        let mut repl = Repl::default();
+        repl.handle_line("INITIALIZE R 1");
+        repl.handle_line("REPEAT 2");
+        assert_eq!(repl.prompt(), "....> ");
+        repl.handle_line("APPLY H R");
+        repl.handle_line("END");
+        assert_eq!(repl.prompt(), "qasm> ");
+        assert_eq!(repl.handle_line("MEASURE R").0, "=> 0");
+    }
+}
@@ -0,0 +1,385 @@
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use quantum_stuff::circuit::Outcome;
+use quantum_stuff::emulator::checker::*;
+use quantum_stuff::emulator::emulator::*;
+use quantum_stuff::emulator::lexer::*;
+
+const USAGE: &str = "Usage: qasm_run [FILE | -] [--check] [--circuit] [--shots N] [--per-shot] [--trace OUT.json] [--break [FILE:]LINE]...
+
+Runs an emulator assembly program (read from FILE, or stdin when FILE is - or missing) and
+prints a histogram of the measurement outcomes over all shots, or every shot (including the
+distributions PEEKed at, in brackets) with --per-shot.
+
+--check only reports problems found without running the program, failing if there are errors.
+--circuit prints the gate list the program compiles to instead of running it.
+--trace writes the state after every instruction of the first shot to OUT.json.
+--break pauses before LINE (1-based) of the program, or of the INCLUDEd FILE, runs in the first
+        shot and prints the registers, then reads [c]ontinue (default), [s]tep or [q]uit from stdin.
+
+Exit codes: 0 success, 1 runtime error (or errors found by --check), 2 usage / input / lexing error.";
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    path: Option<String>,
+    check: bool,
+    circuit: bool,
+    shots: usize,
+    per_shot: bool,
+    trace: Option<String>,
+    // File as given on the command line (None for the program itself) and 1-based line
+    breakpoints: Vec<(Option<String>, usize)>
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        path: None,
+        check: false,
+        circuit: false,
+        shots: 1,
+        per_shot: false,
+        trace: None,
+        breakpoints: Vec::new()
+    };
+
+    let mut arg_iter = args.iter();
+    while let Some(arg) = arg_iter.next() {
+        match arg.as_str() {
+            "--shots" | "-s" => {
+                options.shots = arg_iter.next()
+                    .and_then(|shots| shots.parse().ok())
+                    .filter(|&shots| shots > 0)
+                    .ok_or_else(|| format!("{arg} expects a positive number of shots"))?;
+            },
+            "--check" => { options.check = true; },
+            "--circuit" => { options.circuit = true; },
+            "--per-shot" => { options.per_shot = true; },
+            "--trace" => {
+                options.trace = Some(arg_iter.next().ok_or("--trace expects an output file")?.to_owned());
+            },
+            "--break" | "-b" => {
+                let location = arg_iter.next().ok_or_else(|| format!("{arg} expects a (1-based) line number, optionally preceded by FILE:"))?;
+                let (file, line) = match location.rsplit_once(':') {
+                    Some((file, line)) => (Some(file.to_owned()), line),
+                    None => (None, location.as_str())
+                };
+                let line: usize = line.parse().ok()
+                    .filter(|&line| line > 0)
+                    .ok_or_else(|| format!("{arg} expects a (1-based) line number, optionally preceded by FILE:"))?;
+                options.breakpoints.push((file, line));
+            },
+            "-" => { options.path = None; },
+            flag if flag.starts_with('-') => { return Err(format!("Unknown flag {flag}")); },
+            path => {
+                if options.path.is_some() {
+                    return Err(format!("Unexpected extra argument {path}"));
+                }
+                options.path = Some(path.to_owned());
+            }
+        }
+    }
+
+    Ok(options)
+}
+
+// The name tokens from `file` carry (None for the program at `program`), or None when the program doesn't include it
+fn breakpoint_file(file: &str, program: Option<&str>, tokens: &[Token]) -> Option<Option<PathBuf>> {
+    let canonical = Path::new(file).canonicalize().ok()?;
+    if program.and_then(|program| Path::new(program).canonicalize().ok()).as_ref() == Some(&canonical) {
+        return Some(None);
+    }
+    tokens.iter().filter_map(Token::file)
+        .find(|included| included.canonicalize().ok().as_ref() == Some(&canonical))
+        .map(|included| Some(included.to_path_buf()))
+}
+
+fn read_source(path: Option<&str>) -> io::Result<String> {
+    match path {
+        Some(path) => fs::read_to_string(path),
+        None => {
+            let mut source = String::new();
+            io::stdin().read_to_string(&mut source)?;
+            Ok(source)
+        }
+    }
+}
+
+// Where in which source an error happened
+struct Span<'a> {
+    file: Option<&'a Path>,
+    line: usize,
+    col: usize,
+    width: usize
+}
+
+impl<'a> Span<'a> {
+    fn of_token(token: &'a Token) -> Self {
+        let width = match token.ty {
+            TokenType::NewLine => 1,
+            _ => token.ty.to_string().chars().count()
+        };
+        Self { file: token.file(), line: token.line(), col: token.col(), width }
+    }
+
+    fn of_lex_error(err: &'a LexError) -> Self {
+        Self { file: err.file(), line: err.line(), col: err.col(), width: err.text().chars().count().max(1) }
+    }
+}
+
+fn pretty_error(message: impl Display, span: Option<Span>, source: &str, source_name: &str) -> String {
+    pretty_message("error", message, span, source, source_name)
+}
+
+// Formats a message along with the offending source line, rustc style.
+fn pretty_message(label: impl Display, message: impl Display, span: Option<Span>, source: &str, source_name: &str) -> String {
+    let mut out = format!("{label}: {message}\n");
+    if let Some(span) = span {
+        let line_number = span.line + 1;
+        let gutter = " ".repeat(line_number.to_string().len());
+
+        // Tokens from INCLUDEd files point into those files
+        let (source, source_name) = match span.file {
+            Some(file) => (fs::read_to_string(file).unwrap_or_default(), file.display().to_string()),
+            None => (source.to_owned(), source_name.to_owned())
+        };
+
+        out += &format!("{gutter}--> {source_name}:{line_number}:{}\n", span.col + 1);
+        if let Some(line) = source.lines().nth(span.line) {
+            out += &format!("{gutter} |\n{line_number} | {line}\n{gutter} | {}{}\n", " ".repeat(span.col), "^".repeat(span.width));
+        }
+    }
+    out
+}
+
+// Interactive handler for --break
+fn pause(breakpoint: &Breakpoint) -> DebugAction {
+    match &breakpoint.file {
+        Some(file) => eprintln!("break at {}:{}: {}", file.display(), breakpoint.line + 1, breakpoint.instruction),
+        None => eprintln!("break at line {}: {}", breakpoint.line + 1, breakpoint.instruction)
+    }
+    for (name, qubits, state) in &breakpoint.registers {
+        let probabilities: Vec<String> = state.probabilities_partial(qubits.clone()).iter().map(|prob| format!("{prob:.3}")).collect();
+        eprintln!("  {name} (qubits {}..{}): [{}]", qubits.start, qubits.end, probabilities.join(", "));
+    }
+    eprint!("[c]ontinue, [s]tep, [q]uit? ");
+
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return DebugAction::Continue;
+    }
+    match answer.trim() {
+        "s" | "step" => DebugAction::Step,
+        "q" | "quit" => DebugAction::Abort,
+        _ => DebugAction::Continue
+    }
+}
+
+// Measurement results and peeked distributions of one shot, e.g. `1 [0.500 0.500] 3`
+fn format_outcomes(outcomes: &[Outcome]) -> String {
+    outcomes.iter().map(|outcome| match outcome {
+        Outcome::Measured(result) => result.to_string(),
+        Outcome::Peeked { probabilities, .. } => {
+            format!("[{}]", probabilities.iter().map(|prob| format!("{prob:.3}")).collect::<Vec<_>>().join(" "))
+        }
+    }).collect::<Vec<_>>().join(" ")
+}
+
+fn format_outcome(outcome: &[usize]) -> String {
+    outcome.iter().map(|result| result.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {err}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let source_name = options.path.clone().unwrap_or("<stdin>".to_owned());
+    let source = match read_source(options.path.as_deref()) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("error: could not read {source_name}: {err}");
+            return ExitCode::from(2);
+        }
+    };
+
+    // Scanning the file itself lets INCLUDEs resolve relative to it
+    let scanned = match options.path.as_ref() {
+        Some(path) => scan_file(path),
+        None => scan(&mut source.as_bytes())
+    };
+    let tokens = match scanned {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            eprint!("{}", pretty_error(&err, Some(Span::of_lex_error(&err)), &source, &source_name));
+            return ExitCode::from(2);
+        }
+    };
+
+    if options.check {
+        let diagnostics = check(&tokens);
+        for diagnostic in &diagnostics {
+            eprint!("{}", pretty_message(diagnostic.severity, &diagnostic.message, Some(Span::of_token(diagnostic.token())), &source, &source_name));
+        }
+        let errors = diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Error).count();
+        eprintln!("{errors} errors, {} warnings", diagnostics.len() - errors);
+        return if errors > 0 { ExitCode::from(1) } else { ExitCode::SUCCESS };
+    }
+
+    if options.circuit {
+        match compile(&tokens) {
+            Ok(circuit) => { print!("{circuit}"); },
+            Err(err) => {
+                eprint!("{}", pretty_error(&err, err.token().map(Span::of_token), &source, &source_name));
+                return ExitCode::from(1);
+            }
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    let mut breakpoints = Vec::new();
+    for (file, line) in &options.breakpoints {
+        let file = match file {
+            Some(name) => match breakpoint_file(name, options.path.as_deref(), &tokens) {
+                Some(file) => file,
+                None => {
+                    eprintln!("error: can't break in {name}, it is not part of the program");
+                    return ExitCode::from(2);
+                }
+            },
+            None => None
+        };
+        breakpoints.push((file, line - 1));
+    }
+
+    // Shots only need to be run one by one when they are printed, traced or stepped through
+    let histogram = if options.per_shot || options.trace.is_some() || !options.breakpoints.is_empty() {
+        let mut histogram = Histogram::new();
+        for shot in 0..options.shots {
+            let mut emulator = Emulator::new();
+            if shot == 0 && options.trace.is_some() {
+                emulator.enable_tracing();
+            }
+            // Like the trace, only the first shot is stepped through
+            if shot == 0 && !breakpoints.is_empty() {
+                emulator.set_breakpoints(breakpoints.clone());
+                emulator.on_breakpoint(pause);
+            }
+
+            let outcome = match emulator.execute_outcomes(&tokens) {
+                Ok(outcome) => outcome,
+                Err(err) => {
+                    eprint!("{}", pretty_error(&err, err.token().map(Span::of_token), &source, &source_name));
+                    return ExitCode::from(1);
+                }
+            };
+
+            if let (0, Some(path)) = (shot, options.trace.as_ref()) {
+                if let Err(err) = fs::write(path, trace_to_json(&emulator.take_trace())) {
+                    eprintln!("error: could not write trace to {path}: {err}");
+                    return ExitCode::from(2);
+                }
+            }
+
+            if options.per_shot {
+                println!("{shot}: {}", format_outcomes(&outcome));
+            }
+            histogram.record(outcome.iter().filter_map(Outcome::measured).collect());
+        }
+        histogram
+    } else {
+        match emulate_shots(&tokens, options.shots) {
+            Ok(histogram) => histogram,
+            Err(err) => {
+                eprint!("{}", pretty_error(&err, err.token().map(Span::of_token), &source, &source_name));
+                return ExitCode::from(1);
+            }
+        }
+    };
+
+    if !options.per_shot {
+        println!("{:>10} {:>7}  outcome", "count", "freq");
+        for (outcome, count) in histogram.iter() {
+            println!("{count:>10} {:>6.2}%  {}", 100.0 * histogram.frequency(outcome), format_outcome(outcome));
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_args() {
+        let args: Vec<String> = ["prog.qasm", "--check", "--circuit", "--shots", "10", "--per-shot", "--trace", "out.json", "-b", "3", "--break", "lib/gates.qasm:5"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(parse_args(&args), Ok(Options {
+            path: Some("prog.qasm".to_owned()),
+            check: true,
+            circuit: true,
+            shots: 10,
+            per_shot: true,
+            trace: Some("out.json".to_owned()),
+            breakpoints: vec![(None, 3), (Some("lib/gates.qasm".to_owned()), 5)]
+        }));
+
+        for args in [&["-s", "0"][..], &["--break", "0"], &["--break", "gates.qasm:x"], &["--break", "gates.qasm"], &["--trace"]] {
+            let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+            assert!(parse_args(&args).is_err());
+        }
+
+        assert_eq!(parse_args(&[]), Ok(Options { path: None, check: false, circuit: false, shots: 1, per_shot: false, trace: None, breakpoints: Vec::new() }));
+    }
+
+    #[test]
+    fn test_breakpoint_file() {
+        let dir = env::temp_dir().join(format!("qasm_run_breakpoint_test_{}", std::process::id()));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::write(dir.join("lib").join("prepare.qasm"), "INITIALIZE R 1\n").unwrap();
+        fs::write(dir.join("main.qasm"), "INCLUDE \"lib/prepare.qasm\"\nMEASURE R\n").unwrap();
+        fs::write(dir.join("other.qasm"), "").unwrap();
+        let main = dir.join("main.qasm").to_string_lossy().into_owned();
+        let tokens = scan_file(&main).unwrap();
+
+        let name = |path: PathBuf| path.to_string_lossy().into_owned();
+        assert_eq!(breakpoint_file(&main, Some(&main), &tokens), Some(None));
+        assert_eq!(breakpoint_file(&name(dir.join("lib").join("..").join("lib").join("prepare.qasm")), Some(&main), &tokens), Some(tokens[0].file().map(Path::to_path_buf)));
+        assert_eq!(breakpoint_file(&name(dir.join("other.qasm")), Some(&main), &tokens), None);
+        assert_eq!(breakpoint_file(&name(dir.join("missing.qasm")), Some(&main), &tokens), None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_pretty_error() {
+        let source = "INITIALIZE R 2\nAPPLY H S\n";
+        let tokens = scan(&mut source.as_bytes()).unwrap();
+        let err = emulate(&tokens).unwrap_err();
+
+        let pretty = pretty_error(&err, err.token().map(Span::of_token), source, "prog.qasm");
+        assert!(pretty.contains("--> prog.qasm:2:9"));
+        assert!(pretty.contains("2 | APPLY H S\n  |         ^\n"));
+
+        let source = "INITIALIZE R 2\n  SET R [0120]\n";
+        let err = scan(&mut source.as_bytes()).unwrap_err();
+
+        let pretty = pretty_error(&err, Some(Span::of_lex_error(&err)), source, "prog.qasm");
+        assert!(pretty.contains("--> prog.qasm:2:9"));
+        assert!(pretty.contains("2 |   SET R [0120]\n  |         ^^^^^^\n"));
+    }
+}
@@ -31,7 +31,7 @@ fn shor(N: u64) -> Result<(u64, u64), &'static str> {
     let m_bits = 2 * n_bits;
 
     //Reused Gates
-    let h_m = vec![Gate::hadamard();m_bits].into_iter().reduce(|acc, cur| acc.tensor_product(&cur)).unwrap();
+    let h_m = Kronecker::power(Gate::hadamard().get(), m_bits).unwrap();
     let inverse_qft = qft(1 << m_bits).inverse();
 
     let (a,r) = { 
@@ -57,14 +57,14 @@ fn shor(N: u64) -> Result<(u64, u64), &'static str> {
 
             let mut m_wire = State::from_qubits(vec![false; m_bits].into_iter());
             let n_wire = State::from_qubits(vec![false; n_bits].into_iter());
-            m_wire.apply(&h_m);
+            m_wire.apply_kronecker(&h_m).unwrap();
 
             let mut mn_wire = m_wire.tensor_product(n_wire);
-            mn_wire.apply(&function_oracle);
+            mn_wire.apply(&function_oracle).unwrap();
 
             let (n_measurement, mut m_wire) = mn_wire.measure_partial(m_bits..(m_bits+n_bits));
 
-            m_wire.apply(&inverse_qft);
+            m_wire.apply(&inverse_qft).unwrap();
 
             let x = m_wire.measure();
             dbg!(x);
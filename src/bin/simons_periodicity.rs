@@ -36,8 +36,8 @@ pub fn simons_periodicity(n: usize, f: impl Fn(usize) -> usize) -> usize {
     //Garunteed to be unitary
     let U = Gate::create_oracle_unchecked(n, n, f);
 
-    let H_N = vec![Gate::hadamard();n].into_iter().reduce(|acc, cur| acc.tensor_product(&cur)).unwrap();
-    let H_N_I_N = H_N.tensor_product(&Gate::identity(2usize.pow(n as u32)));
+    let H_N = Kronecker::power(Gate::hadamard().get(), n).unwrap();
+    let H_N_I_N = H_N.tensor_product(&Kronecker::identity(2usize.pow(n as u32)));
 
     //Apparently this only works consistently if the set of answers are linearly independent
 
@@ -48,13 +48,13 @@ pub fn simons_periodicity(n: usize, f: impl Fn(usize) -> usize) -> usize {
         
         // phi_0
 
-        input.apply(&H_N);
+        input.apply_kronecker(&H_N).unwrap();
         let output = State::from_qubits(vec![false; n].into_iter());
         let mut state = input.tensor_product(output);
 
-        state.apply(&U);
+        state.apply(&U).unwrap();
 
-        state.apply(&H_N_I_N);
+        state.apply_kronecker(&H_N_I_N).unwrap();
 
         let res = state.measure_partial(0..n).0;
 
@@ -0,0 +1,47 @@
+use std::env;
+use std::process::ExitCode;
+use quantum_stuff::dynamic::*;
+use quantum_stuff::trotter::*;
+
+const USAGE: &str = "Usage: trotter [HAMILTONIAN] [TIME], e.g. trotter \"0.5*Z0Z1 + 0.3*X0 + 0.3*X1\" 1.0";
+
+// Exit codes: 0 success, 1 a Hamiltonian that can't be simulated, 2 usage error
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+    let hamiltonian: PauliSum = match args.next().as_deref().unwrap_or("0.5*Z0Z1 + 0.5*Z1Z2 + 0.3*X0 + 0.3*X1 + 0.3*X2").parse() {
+        Ok(hamiltonian) => hamiltonian,
+        Err(err) => {
+            eprintln!("Invalid Hamiltonian: {err}\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    let t = match args.next().map(|t| t.parse::<f64>()).unwrap_or(Ok(1.0)) {
+        Ok(t) if t.is_finite() => t,
+        _ => {
+            eprintln!("TIME must be a finite number\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    if let Some(arg) = args.next() {
+        eprintln!("Unexpected extra argument {arg}\n{USAGE}");
+        return ExitCode::from(2);
+    }
+
+    let steps = [1, 2, 4, 8, 16, 32, 64];
+    let errors = trotter_errors(&hamiltonian, t, TrotterOrder::First, steps)
+        .and_then(|first| Ok((first, trotter_errors(&hamiltonian, t, TrotterOrder::Second, steps)?)));
+    let (first, second) = match errors {
+        Ok(errors) => errors,
+        Err(err) => {
+            eprintln!("Can't simulate {hamiltonian}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    println!("H = {hamiltonian}, t = {t}");
+    println!("{:>6} {:>12} {:>12}", "steps", "first", "second");
+    for ((steps, first), (_, second)) in first.into_iter().zip(second) {
+        println!("{steps:>6} {first:>12.3e} {second:>12.3e}");
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,330 @@
+// Gate list representation of quantum programs, independent of how they get simulated.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::rc::Rc;
+
+use crate::complex::*;
+use crate::dynamic::*;
+
+#[derive(Clone)]
+pub enum Instruction {
+    /// Brings unused qubits into the given basis state
+    Initialize { qubits: Vec<usize>, bits: Vec<bool> },
+    /// Applies gate to qubits, the first one acting as the most significant
+    Gate { name: String, gate: Rc<Gate>, qubits: Vec<usize> },
+    /// Measures qubits, the result is part of the outcome of the circuit
+    Measure { qubits: Vec<usize> },
+    /// Records the distribution of qubits without collapsing them
+    Peek { qubits: Vec<usize> },
+    /// Measures qubits (without recording it) and flips them into the given basis state
+    Set { qubits: Vec<usize>, bits: Vec<bool> },
+    /// Measures qubits out, after which they are unused
+    Discard { qubits: Vec<usize> },
+    /// Nothing is moved across it when optimising, no qubits means all of them
+    Barrier { qubits: Vec<usize> }
+}
+
+impl Instruction {
+    pub fn qubits(&self) -> &[usize] {
+        match self {
+            Instruction::Initialize { qubits, .. } | Instruction::Gate { qubits, .. } | Instruction::Measure { qubits } | Instruction::Peek { qubits }
+                | Instruction::Set { qubits, .. } | Instruction::Discard { qubits } | Instruction::Barrier { qubits } => qubits
+        }
+    }
+
+    /// Whether running it always has the same effect, i.e. it doesn't measure anything (peeking doesn't count).
+    pub fn is_deterministic(&self) -> bool {
+        !matches!(self, Instruction::Measure { .. } | Instruction::Set { .. } | Instruction::Discard { .. })
+    }
+}
+
+fn format_bits(bits: &[bool]) -> String {
+    bits.iter().map(|&bit| if bit { '1' } else { '0' }).collect()
+}
+
+// One instruction per line, e.g. `CNOT q0 q2` or `set [01] q1 q3`
+impl Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instruction::Initialize { bits, .. } => write!(f, "init [{}]", format_bits(bits))?,
+            Instruction::Gate { name, .. } => f.write_str(name)?,
+            Instruction::Measure { .. } => f.write_str("measure")?,
+            Instruction::Peek { .. } => f.write_str("peek")?,
+            Instruction::Set { bits, .. } => write!(f, "set [{}]", format_bits(bits))?,
+            Instruction::Discard { .. } => f.write_str("discard")?,
+            Instruction::Barrier { .. } => f.write_str("barrier")?
+        }
+        for qubit in self.qubits() {
+            write!(f, " q{qubit}")?;
+        }
+        Ok(())
+    }
+}
+
+/// What a measurement or peek produced.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Measured(usize),
+    /// Marginal distribution of the peeked qubits, along with their amplitudes if they make up a whole (unentangled) state
+    Peeked { probabilities: Vec<f64>, amplitudes: Option<Vec<C64>> }
+}
+
+impl Outcome {
+    pub fn measured(&self) -> Option<usize> {
+        match self {
+            Outcome::Measured(result) => Some(*result),
+            Outcome::Peeked { .. } => None
+        }
+    }
+}
+
+/// A list of instructions on numbered qubits, along with the registers of the program it was compiled from.
+#[derive(Clone, Default)]
+pub struct Circuit {
+    instructions: Vec<Instruction>,
+    num_qubits: usize,
+    registers: BTreeMap<String, Vec<usize>>
+}
+
+impl Circuit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, instruction: Instruction) {
+        if let Some(&max) = instruction.qubits().iter().max() {
+            self.num_qubits = self.num_qubits.max(max + 1);
+        }
+        self.instructions.push(instruction);
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    pub fn num_qubits(&self) -> usize {
+        self.num_qubits
+    }
+
+    /// Records which qubits make up a register, the first one being the most significant.
+    pub fn name_register(&mut self, name: String, qubits: Vec<usize>) {
+        self.registers.insert(name, qubits);
+    }
+
+    pub fn registers(&self) -> &BTreeMap<String, Vec<usize>> {
+        &self.registers
+    }
+
+    /// Runs the circuit once, returning the outcomes of its measurements and peeks in order.
+    pub fn run(&self) -> Vec<Outcome> {
+        let mut simulator = Simulator::new();
+        self.instructions.iter().filter_map(|instruction| simulator.run(instruction)).collect()
+    }
+
+    /// The matrix of a circuit made only of gates (and barriers), column k being where basis state |k⟩ ends up.
+    pub fn unitary(&self) -> Option<Matrix<C64>> {
+        let n = self.num_qubits;
+        let mut unitary = Matrix::zeroes(1 << n, 1 << n);
+        for k in 0..1 << n {
+            let mut state = State::from_qubits((0..n).map(|qubit| (k >> (n - 1 - qubit)) & 1 == 1));
+            for instruction in &self.instructions {
+                match instruction {
+                    Instruction::Gate { gate, qubits, .. } => state.apply_qubits(qubits, gate).ok()?,
+                    Instruction::Barrier { .. } => {},
+                    _ => return None
+                }
+            }
+            for (r, &amplitude) in state.get().iter().enumerate() {
+                unitary[(r, k)] = amplitude;
+            }
+        }
+        Some(unitary)
+    }
+}
+
+impl Display for Circuit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "qubits {}", self.num_qubits)?;
+        for (name, qubits) in &self.registers {
+            write!(f, "register {name}")?;
+            for qubit in qubits {
+                write!(f, " q{qubit}")?;
+            }
+            writeln!(f)?;
+        }
+        for instruction in &self.instructions {
+            writeln!(f, "{instruction}")?;
+        }
+        Ok(())
+    }
+}
+
+/// State vector backend. Qubits which never interacted are kept in separate states, so e.g. registers of a program cost as much as the largest of them.
+#[derive(Clone, Default)]
+pub struct Simulator {
+    // Each state along with its qubits, most significant first
+    states: Vec<(Vec<usize>, State)>
+}
+
+impl Simulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs one instruction, returning the outcome of measurements and peeks. Qubits which are used without being initialized start out as |0>.
+    pub fn run(&mut self, instruction: &Instruction) -> Option<Outcome> {
+        match instruction {
+            Instruction::Initialize { qubits, bits } => {
+                assert!(qubits.iter().all(|&qubit| self.find(qubit).is_none()), "Initialized qubits which are already in use");
+                self.states.push((qubits.clone(), State::from_qubits(bits.iter().copied())));
+                None
+            },
+            Instruction::Gate { gate, qubits, .. } => {
+                let (index, positions) = self.gather(qubits);
+                self.states[index].1.apply_qubits(&positions, gate).unwrap();
+                None
+            },
+            Instruction::Measure { qubits } => {
+                let (index, positions) = self.gather(qubits);
+                Some(Outcome::Measured(self.states[index].1.measure_qubits_leave_state(&positions)))
+            },
+            Instruction::Peek { qubits } => {
+                let (index, positions) = self.gather(qubits);
+                let (state_qubits, state) = &self.states[index];
+                Some(Outcome::Peeked {
+                    probabilities: state.probabilities_qubits(&positions),
+                    amplitudes: (state_qubits == qubits).then(|| state.get().iter().copied().collect())
+                })
+            },
+            Instruction::Set { qubits, bits } => {
+                let (index, positions) = self.gather(qubits);
+                let state = &mut self.states[index].1;
+                let measured = state.measure_qubits_leave_state(&positions);
+
+                let len = positions.len();
+                for (i, (&position, &bit)) in positions.iter().zip(bits).enumerate() {
+                    if ((measured >> (len - 1 - i)) & 1 == 1) != bit {
+                        state.apply_qubits(&[position], &Gate::not()).unwrap();
+                    }
+                }
+                None
+            },
+            Instruction::Discard { qubits } => {
+                let (index, positions) = self.gather(qubits);
+                let (state_qubits, state) = self.states.swap_remove(index);
+                let (_, remaining) = state.measure_qubits(&positions);
+
+                let remaining_qubits: Vec<usize> = state_qubits.into_iter().filter(|qubit| !qubits.contains(qubit)).collect();
+                if !remaining_qubits.is_empty() {
+                    self.states.push((remaining_qubits, remaining));
+                }
+                None
+            },
+            Instruction::Barrier { .. } => None
+        }
+    }
+
+    /// The state qubit is part of, along with all qubits of that state (most significant first).
+    pub fn state_of(&self, qubit: usize) -> Option<(&[usize], &State)> {
+        self.find(qubit).map(|index| {
+            let (qubits, state) = &self.states[index];
+            (qubits.as_slice(), state)
+        })
+    }
+
+    fn find(&self, qubit: usize) -> Option<usize> {
+        self.states.iter().position(|(qubits, _)| qubits.contains(&qubit))
+    }
+
+    // Makes sure qubits all live in one state (merging states as needed), returning its index and where the qubits are in it
+    fn gather(&mut self, qubits: &[usize]) -> (usize, Vec<usize>) {
+        let mut indices: Vec<Option<usize>> = qubits.iter().map(|&qubit| self.find(qubit)).collect();
+        indices.sort_unstable();
+        indices.dedup();
+
+        let index = match indices.as_slice() {
+            [Some(index)] => *index,
+            _ => {
+                let mut merged_qubits = Vec::new();
+                let mut merged = State::from_qubits(std::iter::empty());
+                for index in indices.iter().rev().flatten() {
+                    let (state_qubits, state) = self.states.remove(*index);
+                    merged_qubits.splice(0..0, state_qubits);
+                    merged = state.tensor_product(merged);
+                }
+                for &qubit in qubits {
+                    if !merged_qubits.contains(&qubit) {
+                        merged_qubits.push(qubit);
+                        merged = merged.tensor_product(State::from_qubit(false));
+                    }
+                }
+                self.states.push((merged_qubits, merged));
+                self.states.len() - 1
+            }
+        };
+
+        let state_qubits = &self.states[index].0;
+        let positions = qubits.iter().map(|qubit| state_qubits.iter().position(|other| other == qubit).unwrap()).collect();
+        (index, positions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gate(name: &str, gate: Gate, qubits: &[usize]) -> Instruction {
+        Instruction::Gate { name: name.to_owned(), gate: Rc::new(gate), qubits: qubits.to_vec() }
+    }
+
+    #[test]
+    fn test_bell_pair() {
+        let mut circuit = Circuit::new();
+        circuit.push(gate("H", Gate::hadamard(), &[3]));
+        circuit.push(gate("CNOT", Gate::cnot(), &[3, 1]));
+        circuit.push(Instruction::Measure { qubits: vec![1] });
+        circuit.push(Instruction::Measure { qubits: vec![3] });
+        assert_eq!(circuit.num_qubits(), 4);
+
+        for _ in 0..50 {
+            let results: Vec<usize> = circuit.run().iter().filter_map(Outcome::measured).collect();
+            assert!(results == vec![0, 0] || results == vec![1, 1]);
+        }
+    }
+
+    #[test]
+    fn test_separate_states() {
+        let mut simulator = Simulator::new();
+        simulator.run(&Instruction::Initialize { qubits: vec![0, 1], bits: vec![false, true] });
+        simulator.run(&Instruction::Initialize { qubits: vec![2], bits: vec![true] });
+        simulator.run(&gate("H", Gate::hadamard(), &[0]));
+        assert_eq!(simulator.state_of(0).unwrap().0, &[0, 1]);
+        assert_eq!(simulator.state_of(2).unwrap().0, &[2]);
+
+        simulator.run(&Instruction::Set { qubits: vec![0, 1], bits: vec![true, false] });
+        let expected = Outcome::Peeked { probabilities: vec![0.0, 0.0, 1.0, 0.0], amplitudes: Some(vec![C64::ZERO, C64::ZERO, C64::ONE, C64::ZERO]) };
+        assert_eq!(simulator.run(&Instruction::Peek { qubits: vec![0, 1] }), Some(expected));
+        assert_eq!(simulator.run(&Instruction::Measure { qubits: vec![1, 0] }), Some(Outcome::Measured(0b01)));
+
+        // Gates across states merge them, measuring out splits them again
+        simulator.run(&gate("CNOT", Gate::cnot(), &[2, 1]));
+        assert_eq!(simulator.state_of(2).unwrap().0, &[0, 1, 2]);
+        simulator.run(&Instruction::Discard { qubits: vec![0, 2] });
+        assert_eq!(simulator.state_of(1).unwrap().0, &[1]);
+        assert!(simulator.state_of(0).is_none());
+        assert_eq!(simulator.run(&Instruction::Measure { qubits: vec![1] }), Some(Outcome::Measured(1)));
+    }
+
+    #[test]
+    fn test_display() {
+        let mut circuit = Circuit::new();
+        circuit.push(Instruction::Initialize { qubits: vec![0, 1], bits: vec![false, true] });
+        circuit.push(gate("CNOT", Gate::cnot(), &[1, 0]));
+        circuit.push(Instruction::Barrier { qubits: vec![] });
+        circuit.push(Instruction::Measure { qubits: vec![0, 1] });
+        circuit.name_register("R".to_owned(), vec![0, 1]);
+
+        assert_eq!(circuit.to_string(), "qubits 2\nregister R q0 q1\ninit [01] q0 q1\nCNOT q1 q0\nbarrier\nmeasure q0 q1\n");
+    }
+}
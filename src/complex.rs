@@ -63,7 +63,97 @@ where Self: Sized
     fn exp(self) -> Self;
     fn modulus_squared(self) -> Self::RealType;
     fn nth_root_of_unity(n: usize) -> Self;
-    
+
+    // Polar form, arg is in (-π, π]
+    fn arg(self) -> Self::RealType;
+    fn from_polar(modulus: Self::RealType, arg: Self::RealType) -> Self;
+    fn to_polar(self) -> (Self::RealType, Self::RealType);
+
+    // Principal branches, cut along the negative real axis
+    fn ln(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn powf(self, exp: Self::RealType) -> Self;
+    fn powc(self, exp: Self) -> Self;
+
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn sinh(self) -> Self;
+    fn cosh(self) -> Self;
+}
+
+// The transcendental functions read the same at either precision
+macro_rules! impl_complex_functions {
+    () => {
+        fn arg(self) -> Self::RealType {
+            self.i.atan2(self.r)
+        }
+
+        fn from_polar(modulus: Self::RealType, arg: Self::RealType) -> Self {
+            let (s, c) = arg.sin_cos();
+            Self::new(modulus * c, modulus * s)
+        }
+
+        fn to_polar(self) -> (Self::RealType, Self::RealType) {
+            (self.r.hypot(self.i), self.arg())
+        }
+
+        // ln 0 is -inf
+        fn ln(self) -> Self {
+            Self::new(self.r.hypot(self.i).ln(), self.arg())
+        }
+
+        // Avoids going through the polar form so perfect squares come out exact
+        fn sqrt(self) -> Self {
+            if self == Self::ZERO {
+                return Self::ZERO;
+            }
+            let t = ((self.r.abs() + self.r.hypot(self.i)) / 2.0).sqrt();
+            if self.r >= 0.0 {
+                Self::new(t, self.i / (2.0 * t))
+            } else {
+                Self::new(self.i.abs() / (2.0 * t), t.copysign(self.i))
+            }
+        }
+
+        // 0^exp as for reals: 0 for exp > 0, 0^0 = 1 and infinite for exp < 0
+        fn powf(self, exp: Self::RealType) -> Self {
+            if self == Self::ZERO {
+                return Self::new(self.r.abs().powf(exp), 0.0);
+            }
+            let (modulus, arg) = self.to_polar();
+            Self::from_polar(modulus.powf(exp), arg * exp)
+        }
+
+        // e^{exp ln z}, so 0^exp is 0 for Re(exp) > 0, 0^0 = 1, infinite for Re(exp) < 0 and NaN for other imaginary exponents
+        fn powc(self, exp: Self) -> Self {
+            if self == Self::ZERO {
+                return if exp == Self::ZERO {
+                    Self::ONE
+                } else if exp.r == 0.0 {
+                    Self::new(Self::RealType::NAN, Self::RealType::NAN)
+                } else {
+                    Self::new(self.r.abs().powf(exp.r), 0.0)
+                };
+            }
+            (exp * self.ln()).exp()
+        }
+
+        fn sin(self) -> Self {
+            Self::new(self.r.sin() * self.i.cosh(), self.r.cos() * self.i.sinh())
+        }
+
+        fn cos(self) -> Self {
+            Self::new(self.r.cos() * self.i.cosh(), -self.r.sin() * self.i.sinh())
+        }
+
+        fn sinh(self) -> Self {
+            Self::new(self.r.sinh() * self.i.cos(), self.r.cosh() * self.i.sin())
+        }
+
+        fn cosh(self) -> Self {
+            Self::new(self.r.cosh() * self.i.cos(), self.r.sinh() * self.i.sin())
+        }
+    };
 }
 
 impl C32 {
@@ -171,6 +261,8 @@ impl Complex for C32 {
         let theta = std::f32::consts::TAU / n as f32;
         Self::exp(Self::new(0.0, theta))
     }
+
+    impl_complex_functions!();
 }
 
 impl Complex for C64 {
@@ -241,6 +333,8 @@ impl Complex for C64 {
         let theta = std::f64::consts::TAU / n as f64;
         Self::new(0.0, theta).exp()
     }
+
+    impl_complex_functions!();
 }
 
 impl From<f64> for C64 {
@@ -628,6 +722,88 @@ mod tests {
         }
     }
 
+    fn close(a: C64, b: C64) -> bool {
+        (a - b).modulus() < 1e-12 * b.modulus().max(1.0)
+    }
+
+    #[test]
+    fn test_polar() {
+        use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI, SQRT_2};
+
+        assert_eq!(C64::new(-1.0, 0.0).arg(), PI);
+        assert_eq!(C64::new(0.0, -2.0).arg(), -FRAC_PI_2);
+        assert!(close(C64::from_polar(SQRT_2, FRAC_PI_4), C64::new(1.0, 1.0)));
+
+        for z in [C64::new(3.0, -4.0), C64::new(-0.5, 0.25), C64::new(0.0, 7.0), C64::new(-2.0, -1e-3)] {
+            let (modulus, arg) = z.to_polar();
+            assert!(close(C64::from_polar(modulus, arg), z));
+        }
+        let (modulus, arg) = C32::new(3.0, 4.0).to_polar();
+        assert!((modulus - 5.0).abs() < 1e-6 && (arg - 4f32.atan2(3.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_ln_sqrt_pow() {
+        use std::f64::consts::{E, FRAC_PI_2, PI};
+        let i = C64::new(0.0, 1.0);
+
+        assert!(close(C64::new(-1.0, 0.0).ln(), C64::new(0.0, PI)));
+        assert!(close(i.ln(), C64::new(0.0, FRAC_PI_2)));
+        assert!(close(C64::new(E, 0.0).ln(), C64::ONE));
+        assert_eq!(C64::new(-4.0, 0.0).sqrt(), C64::new(0.0, 2.0));
+        assert_eq!(C64::new(-5.0, 12.0).sqrt(), C64::new(2.0, 3.0));
+        assert_eq!(C64::new(-5.0, -12.0).sqrt(), C64::new(2.0, -3.0));
+        assert_eq!(C64::ZERO.sqrt(), C64::ZERO);
+
+        // i^i = e^{-π/2}
+        assert!(close(i.powc(i), C64::new((-FRAC_PI_2).exp(), 0.0)));
+        assert_eq!(C64::ZERO.powc(C64::ZERO), C64::ONE);
+        assert_eq!(C64::ZERO.powf(2.5), C64::ZERO);
+        assert_eq!(C64::ZERO.powf(0.0), C64::ONE);
+        assert_eq!(C64::ZERO.powf(-1.0), C64::new(f64::INFINITY, 0.0));
+        assert_eq!(C64::ZERO.powc(C64::new(2.0, 1.0)), C64::ZERO);
+        assert_eq!(C64::ZERO.powc(C64::new(-0.5, 3.0)), C64::new(f64::INFINITY, 0.0));
+        assert!(C64::ZERO.powc(C64::new(0.0, 1.0)).r.is_nan());
+
+        for z in [C64::new(3.0, -4.0), C64::new(-0.5, 0.25), C64::new(0.0, 7.0), C64::new(1e-3, 2.0)] {
+            // Principal logs invert exp whenever the imaginary part is in (-π, π]
+            assert!(close(z.ln().exp(), z));
+            assert!(close(C64::new(z.r, z.i.rem_euclid(PI) - 1.0).exp().ln(), C64::new(z.r, z.i.rem_euclid(PI) - 1.0)));
+            assert!(close(z.sqrt() * z.sqrt(), z));
+            assert!(z.sqrt().r >= 0.0);
+            assert!(close(z.powf(0.5), z.sqrt()));
+            assert!(close(z.powf(3.0), z.pow(3)));
+            assert!(close(z.powc(C64::new(-2.0, 0.0)), C64::ONE / z.pow(2)));
+            assert!(close(z.powf(1.0 / 3.0).pow(3), z));
+            // z^{a + b} = z^a z^b
+            let (a, b) = (C64::new(0.3, -1.2), C64::new(-0.7, 0.4));
+            assert!(close(z.powc(a + b), z.powc(a) * z.powc(b)));
+        }
+
+        assert_eq!(C32::new(-9.0, 0.0).sqrt(), C32::new(0.0, 3.0));
+        assert!((C32::new(0.0, 1.0).powc(C32::new(0.0, 1.0)) - C32::new((-std::f32::consts::FRAC_PI_2).exp(), 0.0)).modulus() < 1e-6);
+    }
+
+    #[test]
+    fn test_trig() {
+        let i = C64::new(0.0, 1.0);
+        for z in [C64::new(0.3, -1.1), C64::new(-2.0, 0.5), C64::new(0.0, 3.0), C64::new(1.5, 0.0)] {
+            assert!(close(z.sin() * z.sin() + z.cos() * z.cos(), C64::ONE));
+            assert!(close(z.cosh() * z.cosh() - z.sinh() * z.sinh(), C64::ONE));
+            // Euler, and the trig/hyperbolic swaps under z -> iz
+            assert!(close((i * z).exp(), z.cos() + i * z.sin()));
+            assert!(close((i * z).sin(), i * z.sinh()));
+            assert!(close((i * z).cos(), z.cosh()));
+            assert!(close(z.sinh(), (z.exp() - (-z).exp()) / C64::new(2.0, 0.0)));
+            assert!(close((z + z).sin(), C64::new(2.0, 0.0) * z.sin() * z.cos()));
+        }
+        assert!(close(C64::new(1.5, 0.0).sin(), C64::new(1.5f64.sin(), 0.0)));
+
+        let z = C32::new(0.7, -0.4);
+        assert!((z.sin() * z.sin() + z.cos() * z.cos() - C32::ONE).modulus() < 1e-6);
+        assert!((z.cosh() * z.cosh() - z.sinh() * z.sinh() - C32::ONE).modulus() < 1e-6);
+    }
+
     // #[test]
     // fn exc_1_3_8() {
     //     let z = c64!(1 + -1.0 i);
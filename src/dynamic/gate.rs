@@ -3,6 +3,7 @@ use std::ops::Mul;
 
 use super::matrix::*;
 use crate::complex::*;
+use crate::error::Error;
 
 #[derive(Clone)]
 pub struct Gate(Matrix<C64>);
@@ -43,7 +44,7 @@ impl Gate {
     
     pub fn phase_shift(theta: f64) -> Self {
         let mut mat = dmat64![[1;0],[0;1]];
-        *mat.get_mut(1, 1) = C64::new(0.0, theta).exp();
+        mat[(1, 1)] = C64::new(0.0, theta).exp();
         Self(mat)
     }
 
@@ -59,6 +60,23 @@ impl Gate {
         Self(dmat64![[1;0],[0;-1]])
     }
 
+    // exp(-iθX/2)
+    pub fn rotation_x(theta: f64) -> Self {
+        let (s, c) = (theta / 2.0).sin_cos();
+        Self(Matrix::from([[C64::new(c, 0.0), C64::new(0.0, -s)], [C64::new(0.0, -s), C64::new(c, 0.0)]]))
+    }
+
+    // exp(-iθY/2)
+    pub fn rotation_y(theta: f64) -> Self {
+        let (s, c) = (theta / 2.0).sin_cos();
+        Self(Matrix::from([[C64::new(c, 0.0), C64::new(-s, 0.0)], [C64::new(s, 0.0), C64::new(c, 0.0)]]))
+    }
+
+    // exp(-iθZ/2)
+    pub fn rotation_z(theta: f64) -> Self {
+        Self(Matrix::from([[C64::new(0.0, -theta / 2.0).exp(), C64::ZERO], [C64::ZERO, C64::new(0.0, theta / 2.0).exp()]]))
+    }
+
     pub fn swap() -> Self {
         Self(dmat64![[1;0;0;0],[0;0;1;0],[0;1;0;0],[0;0;0;1]])
     }
@@ -71,12 +89,12 @@ impl Gate {
         let og_n = og.dim();
         let mut mat = Matrix::zeroes(og_n * 2, og_n * 2);
         for i in 0..og_n {
-            *mat.get_mut(i,i) = C64::ONE;
+            mat[(i,i)] = C64::ONE;
         }
         
         for r in 0..og_n {
             for c in 0..og_n {
-                *mat.get_mut(og_n + r, og_n + c) = og.0.get(r, c);
+                mat[(og_n + r, og_n + c)] = og.0[(r, c)];
             }
         }
 
@@ -89,7 +107,7 @@ impl Gate {
         for x in 0..(1 << input_bits) {
             let f_x = f(x);
             for y in 0..(1 << output_bits) {
-                *mat.get_mut((x << output_bits) + (y ^ f_x), (x << output_bits) + y) = C64::ONE;
+                mat[((x << output_bits) + (y ^ f_x), (x << output_bits) + y)] = C64::ONE;
             }
         }
         Self::try_from(mat).unwrap()
@@ -101,7 +119,7 @@ impl Gate {
         for x in 0..(1 << input_bits) {
             let f_x = f(x);
             for y in 0..(1 << output_bits) {
-                *mat.get_mut((x << output_bits) + (y ^ f_x), (x << output_bits) + y) = C64::ONE;
+                mat[((x << output_bits) + (y ^ f_x), (x << output_bits) + y)] = C64::ONE;
             }
         }
         unsafe { Self::from_matrix_unchecked(mat) }
@@ -115,18 +133,27 @@ impl Gate {
 }
 
 impl TryFrom<Matrix<C64>> for Gate {
-    type Error = ();
+    type Error = Error;
     fn try_from(value: Matrix<C64>) -> Result<Self, Self::Error> {
+        let (m, n) = value.dim();
+        if m != n {
+            return Err(Error::DimensionMismatch { left: (m, n), right: (n, m) });
+        }
+
         if value.is_unitary() {
             Ok(Gate(value))
         } else {
-            Err(())
+            let product = (&value * &value.adjoint())?;
+            let deviation = (0..n).flat_map(|r| (0..n).map(move |c| (r, c)))
+                .map(|(r, c)| (product[(r, c)] - if r == c { C64::ONE } else { C64::ZERO }).modulus())
+                .fold(0.0, f64::max);
+            Err(Error::NotUnitary { deviation })
         }
     }
 }
 
 impl Mul for &Gate {
-    type Output = Result<Gate, ()>;
+    type Output = Result<Gate, Error>;
 
     fn mul(self, rhs: Self) -> Self::Output {
         let mat = (&self.0 * &rhs.0)?; 
@@ -147,5 +174,10 @@ mod tests {
         let phase_shift = [[C64::ONE, C64::ZERO], [C64::ZERO, C64::new(0.0, theta).exp()]];
         let mat = Matrix::from(phase_shift);
         let op: Gate = mat.try_into().unwrap();
+
+        let not_unitary = Gate::try_from(dmat64![[1;1],[0;1]]);
+        assert!(matches!(not_unitary, Err(Error::NotUnitary { deviation }) if (deviation - 1.0).abs() < 1e-9));
+        assert!(matches!(Gate::try_from(Matrix::<C64>::zeroes(2, 3)), Err(Error::DimensionMismatch { .. })));
+        assert!(matches!(&Gate::hadamard() * &Gate::cnot(), Err(Error::DimensionMismatch { left: (2, 2), right: (4, 4) })));
     }
 }
\ No newline at end of file
@@ -0,0 +1,94 @@
+use super::gate::*;
+use super::matrix::*;
+use super::state::*;
+use super::vector::*;
+use crate::complex::*;
+use crate::error::Error;
+
+/// A hermitian operator generating the time evolution U(t) = exp(-iHt).
+#[derive(Clone, Debug)]
+pub struct Hamiltonian(Matrix<C64>);
+
+impl Hamiltonian {
+    pub fn get(&self) -> &Matrix<C64> {
+        &self.0
+    }
+
+    pub fn dim(&self) -> usize {
+        self.0.dim().0
+    }
+
+    // Fails if round off in expm left the result further from unitary than Gate allows
+    pub fn evolution(&self, t: f64) -> Result<Gate, Error> {
+        let exponent = self.0.clone() * C64::new(0.0, -t);
+        Gate::try_from(exponent.expm()?)
+    }
+
+    pub fn evolve(&self, state: &mut State, t: f64) -> Result<(), Error> {
+        state.apply(&self.evolution(t)?)
+    }
+
+    // Energies (ascending) with their eigenstates, can't fail as the matrix was checked to be hermitian on construction
+    pub fn eigenpairs(&self) -> Vec<(f64, Vector<C64>)> {
+        self.0.eigenpairs_hermitian().unwrap()
+    }
+}
+
+impl TryFrom<Matrix<C64>> for Hamiltonian {
+    type Error = Error;
+    fn try_from(value: Matrix<C64>) -> Result<Self, Self::Error> {
+        if !value.is_hermitian() {
+            return Err(Error::NotHermitian { deviation: value.hermitian_deviation()? });
+        }
+        Ok(Self(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::complex::*;
+    use crate::dynamic::*;
+    use crate::error::Error;
+
+    #[test]
+    fn test_evolution() {
+        // exp(-iXt) = cos(t) I - i sin(t) X, a quarter period takes |0⟩ to -i|1⟩
+        let x = Hamiltonian::try_from(Gate::pauli_x().get().clone()).unwrap();
+        let u = x.evolution(0.3).unwrap();
+        let expected = (Matrix::eye(2) * C64::new(0.3_f64.cos(), 0.0) + &(Gate::pauli_x().get().clone() * C64::new(0.0, -0.3_f64.sin()))).unwrap();
+        assert!(u.get().data.iter().zip(&expected.data).all(|(a, b)| (*a - *b).modulus() < 1e-12));
+
+        let mut state = State::from_qubit(false);
+        x.evolve(&mut state, PI / 2.0).unwrap();
+        assert!((state.get()[1] - C64::new(0.0, -1.0)).modulus() < 1e-12);
+
+        // Forwards then backwards in time is the identity, also for a norm large enough to need squaring
+        let h = Hamiltonian::try_from(dmat64![[2;0,1;0],[0,-1;-3;1,1],[0;1,-1;5]] * c64!(7)).unwrap();
+        let there = h.evolution(1.5).unwrap();
+        let back = h.evolution(-1.5).unwrap();
+        let round_trip = (there.get() * back.get()).unwrap();
+        assert!(round_trip.data.iter().zip(&Matrix::<C64>::eye(3).data).all(|(a, b)| (*a - *b).modulus() < 1e-9));
+
+        // Eigenstates only pick up a phase
+        let (energy, eigenstate) = h.eigenpairs().remove(0);
+        let evolved = (there.get() * &eigenstate).unwrap();
+        let phase = C64::new(0.0, -energy * 1.5).exp();
+        assert!(evolved.iter().zip(eigenstate.iter()).all(|(a, b)| (*a - *b * phase).modulus() < 1e-9));
+
+        assert!(matches!(Hamiltonian::try_from(dmat64![[0;1],[0;0]]), Err(Error::NotHermitian { .. })));
+        // Round off relative to large energies is fine
+        assert!(Hamiltonian::try_from(dmat64![[3e15; 1e15,2e15],[1e15,-2e15+0.25; -4e15]]).is_ok());
+    }
+
+    #[test]
+    fn test_expm() {
+        assert!(Matrix::<C64>::zeroes(3, 3).expm().unwrap().is_identity());
+
+        let close = |a: &Matrix<C64>, b: &Matrix<C64>| a.data.iter().zip(&b.data).all(|(x, y)| (*x - *y).modulus() < 1e-9 * y.modulus().max(1.0));
+        assert!(close(&dmat64![[10;0],[0;-3]].expm().unwrap(), &dmat64![[10.0_f64.exp();0],[0;(-3.0_f64).exp()]]));
+        assert!(close(&dmat64![[0;1],[0;0]].expm().unwrap(), &dmat64![[1;1],[0;1]]));
+        assert!(matches!(Matrix::<C64>::zeroes(2, 3).expm(), Err(Error::DimensionMismatch { .. })));
+    }
+}
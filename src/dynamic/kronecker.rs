@@ -0,0 +1,203 @@
+use std::ops::Mul;
+
+use super::gate::*;
+use super::matrix::*;
+use super::vector::*;
+use crate::complex::*;
+use crate::error::Error;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Factor<F: Complex> {
+    // Kept as just a size so large identities cost nothing to store or apply
+    Identity(usize),
+    Dense(Matrix<F>)
+}
+
+impl<F: Complex> Factor<F> {
+    fn dim(&self) -> usize {
+        match self {
+            Factor::Identity(dim) => *dim,
+            Factor::Dense(matrix) => matrix.dim().0
+        }
+    }
+}
+
+/// Lazy tensor product A₀ ⊗ A₁ ⊗ ... of square factors, A₀ acting on the most significant part of the index.
+/// Applying it multiplies by one factor at a time, so H^{⊗n} takes O(n 2^n) work and the factors' memory rather than a 4^n matrix.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Kronecker<F: Complex> {
+    factors: Vec<Factor<F>>
+}
+
+impl<F: Complex> Kronecker<F> {
+    pub fn new(factors: Vec<Matrix<F>>) -> Result<Self, Error> {
+        factors.into_iter().try_fold(Self { factors: Vec::new() }, |mut acc, factor| {
+            let (m, n) = factor.dim();
+            if m != n {
+                return Err(Error::DimensionMismatch { left: (m, n), right: (n, m) });
+            }
+            acc.push(Factor::Dense(factor));
+            Ok(acc)
+        })
+    }
+
+    pub fn identity(dim: usize) -> Self {
+        let mut result = Self { factors: Vec::new() };
+        result.push(Factor::Identity(dim));
+        result
+    }
+
+    // factor ⊗ factor ⊗ ... n times
+    pub fn power(factor: &Matrix<F>, n: usize) -> Result<Self, Error> {
+        Self::new(vec![factor.clone(); n])
+    }
+
+    // Neighbouring identities are merged into one
+    fn push(&mut self, factor: Factor<F>) {
+        match (self.factors.last_mut(), &factor) {
+            (Some(Factor::Identity(last)), Factor::Identity(dim)) => *last *= dim,
+            _ => self.factors.push(factor)
+        }
+    }
+
+    pub fn tensor_product(&self, rhs: &Self) -> Self {
+        let mut result = self.clone();
+        for factor in &rhs.factors {
+            result.push(factor.clone());
+        }
+        result
+    }
+
+    pub fn dim(&self) -> usize {
+        self.factors.iter().map(Factor::dim).product()
+    }
+
+    // The factors that aren't identities
+    pub fn factors(&self) -> impl Iterator<Item = &Matrix<F>> {
+        self.factors.iter().filter_map(|factor| match factor {
+            Factor::Identity(_) => None,
+            Factor::Dense(matrix) => Some(matrix)
+        })
+    }
+
+    pub fn adjoint(&self) -> Self {
+        let factors = self.factors.iter().map(|factor| match factor {
+            Factor::Identity(dim) => Factor::Identity(*dim),
+            Factor::Dense(matrix) => Factor::Dense(matrix.adjoint())
+        }).collect();
+        Self { factors }
+    }
+
+    // Materializes the whole product, only meant for small operators
+    pub fn to_dense(&self) -> Matrix<F> {
+        self.factors.iter().fold(Matrix::eye(1), |acc, factor| match factor {
+            Factor::Identity(dim) => acc.tensor_product(&Matrix::eye(*dim)),
+            Factor::Dense(matrix) => acc.tensor_product(matrix)
+        })
+    }
+
+    // A product of unitaries is unitary
+    pub fn is_unitary(&self) -> bool {
+        self.factors().all(Matrix::is_unitary)
+    }
+}
+
+impl From<&Gate> for Kronecker<C64> {
+    fn from(value: &Gate) -> Self {
+        Self { factors: vec![Factor::Dense(value.get().clone())] }
+    }
+}
+
+// Multiplies the vector in place by I_left ⊗ A ⊗ I_right, each block of dim(A) * right entries holds right interleaved vectors A acts on
+fn apply_factor<F: Complex>(data: &mut [F], factor: &Matrix<F>, right: usize) {
+    let dim = factor.dim().0;
+    let mut gathered = vec![F::ZERO; dim];
+    for block in data.chunks_mut(dim * right) {
+        for r in 0..right {
+            for (j, entry) in gathered.iter_mut().enumerate() {
+                *entry = block[j * right + r];
+            }
+            for (i, row) in factor.data.chunks(dim).enumerate() {
+                block[i * right + r] = row.iter().zip(&gathered).fold(F::ZERO, |acc, (&a, &b)| acc + a * b);
+            }
+        }
+    }
+}
+
+//Action on Vectors
+impl<F: Complex> Mul<&Vector<F>> for &Kronecker<F> {
+    type Output = Result<Vector<F>, Error>;
+
+    fn mul(self, rhs: &Vector<F>) -> Self::Output {
+        let dim = self.dim();
+        if dim != rhs.dim() { return Err(Error::DimensionMismatch { left: (dim, dim), right: (rhs.dim(), 1) }); }
+        // Some factor is empty, so is the vector and there is nothing to divide between the factors
+        if dim == 0 { return Ok(rhs.clone()); }
+
+        let mut result = rhs.clone();
+        let mut right = dim;
+        for factor in &self.factors {
+            right /= factor.dim();
+            if let Factor::Dense(matrix) = factor {
+                apply_factor(&mut result.data, matrix, right);
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::complex::*;
+    use crate::dynamic::*;
+    use crate::error::Error;
+
+    #[test]
+    fn test_kronecker() {
+        let a = dmat64![[1;2,1],[0;-1]];
+        let b = dmat64![[0;1;0],[2;0;0,1],[1;1;1]];
+        let op = Kronecker::new(vec![a.clone(), b.clone()]).unwrap().tensor_product(&Kronecker::identity(2));
+        assert_eq!(op.dim(), 12);
+        assert_eq!(op.to_dense(), a.tensor_product(&b).tensor_product(&Matrix::eye(2)));
+        assert_eq!(op.adjoint().to_dense(), op.to_dense().adjoint());
+
+        let v = Vector::from_iter((0..12).map(|i| c64!(i, 1 - i)), Some(12));
+        assert_eq!((&op * &v).unwrap(), (&op.to_dense() * &v).unwrap());
+        let op = Kronecker::identity(3).tensor_product(&Kronecker::new(vec![a.clone()]).unwrap()).tensor_product(&Kronecker::identity(2));
+        assert_eq!((&op * &v).unwrap(), (&op.to_dense() * &v).unwrap());
+        assert_eq!(op.factors().count(), 1);
+
+        assert!((&op * &dvec64![1;0]).is_err());
+        assert!(matches!(Kronecker::new(vec![dmat64![[1;0]]]), Err(Error::DimensionMismatch { .. })));
+        // Identities merge
+        assert_eq!(Kronecker::<C64>::identity(2).tensor_product(&Kronecker::identity(4)), Kronecker::identity(8));
+
+        // Empty factors make an empty operator rather than dividing by zero
+        let empty = Vector::<C64>::from_iter(std::iter::empty(), None);
+        assert_eq!((&Kronecker::identity(0) * &empty).unwrap(), empty);
+        let op = Kronecker::new(vec![a.clone()]).unwrap().tensor_product(&Kronecker::identity(0));
+        assert_eq!(op.dim(), 0);
+        assert_eq!((&op * &empty).unwrap(), empty);
+        assert!((&op * &v).is_err());
+    }
+
+    #[test]
+    fn test_hadamard_power() {
+        // H^{⊗16} on |0...0⟩ is the uniform superposition, the dense matrix would take 64 GiB
+        let n = 16;
+        let h_n = Kronecker::power(Gate::hadamard().get(), n).unwrap();
+        assert!(h_n.is_unitary());
+
+        let mut state = State::from_qubits(vec![false; n].into_iter());
+        state.apply_kronecker(&h_n).unwrap();
+        let amplitude = 1.0 / ((1 << n) as f64).sqrt();
+        assert!(state.get().iter().all(|entry| (*entry - c64!(amplitude)).modulus() < 1e-12));
+
+        // And back again
+        state.apply_kronecker(&h_n).unwrap();
+        assert!((state.get()[0] - C64::ONE).modulus() < 1e-9);
+
+        let not_unitary = Kronecker::new(vec![dmat64![[1;1],[0;1]]]).unwrap();
+        assert!(matches!(State::from_qubit(false).apply_kronecker(&not_unitary), Err(Error::NotUnitary { .. })));
+    }
+}
@@ -0,0 +1,581 @@
+// Decompositions of complex matrices, written out by hand rather than pulling in faer.
+
+use super::matrix::*;
+use super::vector::*;
+use crate::complex::*;
+use crate::error::Error;
+
+// Sweeps over all off diagonal pairs before giving up on convergence, Jacobi usually needs well under 10
+const MAX_SWEEPS: usize = 64;
+
+// Degree of the Padé approximant in expm, plenty for double precision once the norm is scaled below 1/2
+const PADE_DEGREE: i32 = 6;
+
+/// LU decomposition with partial pivoting, P A = L U with L unit lower triangular.
+pub struct Lu {
+    // L below the diagonal, U on and above it
+    lu: Matrix<C64>,
+    // Row i of P A is row permutation[i] of A
+    permutation: Vec<usize>,
+    // Parity of the permutation, as the sign it puts on the determinant
+    sign: f64,
+    singular: Option<usize>
+}
+
+impl Lu {
+    pub fn l(&self) -> Matrix<C64> {
+        let n = self.lu.dim().0;
+        let mut l = Matrix::eye(n);
+        for r in 0..n {
+            for c in 0..r {
+                l[(r, c)] = self.lu[(r, c)];
+            }
+        }
+        l
+    }
+
+    pub fn u(&self) -> Matrix<C64> {
+        let n = self.lu.dim().0;
+        let mut u = Matrix::zeroes(n, n);
+        for r in 0..n {
+            for c in r..n {
+                u[(r, c)] = self.lu[(r, c)];
+            }
+        }
+        u
+    }
+
+    pub fn permutation(&self) -> &[usize] {
+        &self.permutation
+    }
+
+    pub fn determinant(&self) -> C64 {
+        (0..self.lu.dim().0).fold(C64::new(self.sign, 0.0), |acc, i| acc * self.lu[(i, i)])
+    }
+
+    pub fn solve(&self, b: &Vector<C64>) -> Result<Vector<C64>, Error> {
+        let n = self.lu.dim().0;
+        if b.dim() != n {
+            return Err(Error::DimensionMismatch { left: self.lu.dim(), right: (b.dim(), 1) });
+        }
+        if let Some(column) = self.singular {
+            return Err(Error::Singular { column });
+        }
+
+        // L y = P b, then U x = y
+        let mut x = Vector::from_iter(self.permutation.iter().map(|&r| b[r]), Some(n));
+        for r in 0..n {
+            for c in 0..r {
+                let (l, y) = (self.lu[(r, c)], x[c]);
+                x[r] -= l * y;
+            }
+        }
+        for r in (0..n).rev() {
+            for c in r + 1..n {
+                let (u, y) = (self.lu[(r, c)], x[c]);
+                x[r] -= u * y;
+            }
+            x[r] /= self.lu[(r, r)];
+        }
+        Ok(x)
+    }
+
+    pub fn inverse(&self) -> Result<Matrix<C64>, Error> {
+        let n = self.lu.dim().0;
+        let mut inverse = Matrix::zeroes(n, n);
+        for c in 0..n {
+            let column = self.solve(&Matrix::<C64>::eye(n).col(c).unwrap().to_vector())?;
+            for r in 0..n {
+                inverse[(r, c)] = column[r];
+            }
+        }
+        Ok(inverse)
+    }
+}
+
+/// Thin singular value decomposition A = U Σ V†, the min(m, n) singular values are in descending order and U, V have orthonormal columns.
+pub struct Svd {
+    pub u: Matrix<C64>,
+    pub singular_values: Vec<f64>,
+    pub v: Matrix<C64>
+}
+
+// Entries at most this big (relative to the largest one) count as zero pivots
+fn pivot_tolerance(m: &Matrix<C64>) -> f64 {
+    let (rows, cols) = m.dim();
+    m.data.iter().map(|entry| entry.modulus()).fold(0.0, f64::max) * f64::EPSILON * rows.max(cols) as f64
+}
+
+impl Matrix<C64> {
+    pub fn lu(&self) -> Result<Lu, Error> {
+        let (m, n) = self.dim();
+        if m != n {
+            return Err(Error::DimensionMismatch { left: (m, n), right: (n, m) });
+        }
+
+        let tolerance = pivot_tolerance(self);
+        let mut lu = self.clone();
+        let mut permutation: Vec<usize> = (0..n).collect();
+        let (mut sign, mut singular) = (1.0, None);
+
+        for k in 0..n {
+            let pivot = (k..n).max_by(|&a, &b| lu[(a, k)].modulus().total_cmp(&lu[(b, k)].modulus())).unwrap();
+            if lu[(pivot, k)].modulus() <= tolerance {
+                singular = singular.or(Some(k));
+                continue;
+            }
+            if pivot != k {
+                for c in 0..n {
+                    let (a, b) = (lu[(k, c)], lu[(pivot, c)]);
+                    lu[(k, c)] = b;
+                    lu[(pivot, c)] = a;
+                }
+                permutation.swap(k, pivot);
+                sign = -sign;
+            }
+
+            for r in k + 1..n {
+                let factor = lu[(r, k)] / lu[(k, k)];
+                lu[(r, k)] = factor;
+                for c in k + 1..n {
+                    let u = lu[(k, c)];
+                    lu[(r, c)] -= factor * u;
+                }
+            }
+        }
+
+        Ok(Lu { lu, permutation, sign, singular })
+    }
+
+    pub fn determinant(&self) -> Result<C64, Error> {
+        let lu = self.lu()?;
+        Ok(if lu.singular.is_some() { C64::ZERO } else { lu.determinant() })
+    }
+
+    pub fn solve(&self, b: &Vector<C64>) -> Result<Vector<C64>, Error> {
+        self.lu()?.solve(b)
+    }
+
+    pub fn inverse(&self) -> Result<Self, Error> {
+        self.lu()?.inverse()
+    }
+
+    /// Householder QR, A = Q R with Q (m x m) unitary and R (m x n) upper triangular.
+    pub fn qr(&self) -> (Self, Self) {
+        let (m, n) = self.dim();
+        let mut q = Matrix::<C64>::eye(m);
+        let mut r = self.clone();
+
+        for k in 0..n.min(m.saturating_sub(1)) {
+            let norm = (k..m).map(|i| r[(i, k)].modulus_squared()).sum::<f64>().sqrt();
+            if norm == 0.0 {
+                continue;
+            }
+
+            // v = x - alpha e1, alpha taking the phase opposite to x0 to avoid cancellation
+            let x0 = r[(k, k)];
+            let phase = if x0.modulus() == 0.0 { C64::ONE } else { x0 * C64::new(1.0 / x0.modulus(), 0.0) };
+            let alpha = -phase * C64::new(norm, 0.0);
+            let mut v: Vec<C64> = (k..m).map(|i| r[(i, k)]).collect();
+            v[0] -= alpha;
+            let v_norm_squared: f64 = v.iter().map(|entry| entry.modulus_squared()).sum();
+
+            // H = I - 2 v v† / v†v, R <- H R and Q <- Q H
+            for c in 0..n {
+                let dot = v.iter().enumerate().fold(C64::ZERO, |acc, (i, vi)| acc + vi.conjugate() * r[(k + i, c)]);
+                let scale = dot * C64::new(2.0 / v_norm_squared, 0.0);
+                for (i, vi) in v.iter().enumerate() {
+                    r[(k + i, c)] -= *vi * scale;
+                }
+            }
+            for row in 0..m {
+                let dot = v.iter().enumerate().fold(C64::ZERO, |acc, (i, vi)| acc + q[(row, k + i)] * *vi);
+                let scale = dot * C64::new(2.0 / v_norm_squared, 0.0);
+                for (i, vi) in v.iter().enumerate() {
+                    q[(row, k + i)] -= scale * vi.conjugate();
+                }
+            }
+            for i in k + 1..m {
+                r[(i, k)] = C64::ZERO;
+            }
+        }
+
+        (q, r)
+    }
+
+    pub fn svd(&self) -> Svd {
+        let (m, n) = self.dim();
+        if m < n {
+            // A† = V Σ U†
+            let Svd { u, singular_values, v } = self.adjoint().svd();
+            return Svd { u: v, singular_values, v: u };
+        }
+
+        // One sided Jacobi, rotates columns of A until they're orthogonal, their norms are then the singular values
+        let mut a = self.clone();
+        let mut v = Matrix::<C64>::eye(n);
+        // Columns with less than this squared norm are zero for all purposes
+        let negligible = (pivot_tolerance(self) * n as f64).powi(2);
+        for _ in 0..MAX_SWEEPS {
+            let mut rotated = false;
+            for p in 0..n {
+                for q in p + 1..n {
+                    let (column_p, column_q) = (a.col(p).unwrap(), a.col(q).unwrap());
+                    let (alpha, beta) = (column_p.dot(&column_p).unwrap().r, column_q.dot(&column_q).unwrap().r);
+                    let gamma = column_p.dot(&column_q).unwrap();
+                    if alpha.min(beta) <= negligible || gamma.modulus() <= f64::EPSILON * (alpha * beta).sqrt() {
+                        continue;
+                    }
+
+                    let rotation = Rotation::new(alpha, beta, gamma);
+                    rotation.columns(&mut a, p, q);
+                    rotation.columns(&mut v, p, q);
+                    rotated = true;
+                }
+            }
+            if !rotated {
+                break;
+            }
+        }
+
+        let norms: Vec<f64> = a.col_iter().map(|column| column.dot(&column).unwrap().r.sqrt()).collect();
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|&x, &y| norms[y].total_cmp(&norms[x]));
+        let tolerance = norms.iter().fold(0.0, |acc: f64, &norm| acc.max(norm)) * f64::EPSILON * m as f64;
+
+        let (mut u, mut sorted_v) = (Matrix::zeroes(m, n), Matrix::zeroes(n, n));
+        let mut singular_values = Vec::with_capacity(n);
+        for (j, &column) in order.iter().enumerate() {
+            let sigma = norms[column];
+            singular_values.push(sigma);
+            for r in 0..n {
+                sorted_v[(r, j)] = v[(r, column)];
+            }
+            if sigma > tolerance {
+                for r in 0..m {
+                    u[(r, j)] = a[(r, column)] / C64::new(sigma, 0.0);
+                }
+            } else {
+                complete_column(&mut u, j);
+            }
+        }
+
+        Svd { u, singular_values, v: sorted_v }
+    }
+
+    /// Matrix exponential, by scaling and squaring a diagonal Padé approximant (Golub & Van Loan 11.3.1).
+    pub fn expm(&self) -> Result<Self, Error> {
+        let (m, n) = self.dim();
+        if m != n {
+            return Err(Error::DimensionMismatch { left: (m, n), right: (n, m) });
+        }
+
+        // Scale by 2^-s so the infinity norm is at most 1/2, then square s times
+        let norm = self.row_iter().map(|row| row.iter().map(|entry| entry.modulus()).sum::<f64>()).fold(0.0, f64::max);
+        let s = if norm > 0.0 { (norm.log2().floor() as i32 + 2).max(0) } else { 0 };
+        let a = self.clone() * C64::new(0.5_f64.powi(s), 0.0);
+
+        // N(A) / D(A) = (Σ c_k A^k) / (Σ (-1)^k c_k A^k)
+        let mut c = 0.5;
+        let mut x = a.clone();
+        let mut numerator = (Matrix::eye(n) + &(x.clone() * C64::new(c, 0.0)))?;
+        let mut denominator = (Matrix::eye(n) - &(x.clone() * C64::new(c, 0.0)))?;
+        for k in 2..=PADE_DEGREE {
+            c *= (PADE_DEGREE - k + 1) as f64 / (k * (2 * PADE_DEGREE - k + 1)) as f64;
+            x = (&a * &x)?;
+            let term = x.clone() * C64::new(c, 0.0);
+            numerator += &term;
+            if k % 2 == 0 {
+                denominator += &term;
+            } else {
+                denominator -= &term;
+            }
+        }
+
+        let mut exp = (&denominator.inverse()? * &numerator)?;
+        for _ in 0..s {
+            exp = (&exp * &exp)?;
+        }
+        Ok(exp)
+    }
+
+    // Number of pivots found by gaussian elimination with partial pivoting
+    pub fn rank(&self) -> usize {
+        let (m, n) = self.dim();
+        let tolerance = pivot_tolerance(self);
+        let mut a = self.clone();
+        let mut rank = 0;
+
+        for c in 0..n {
+            if rank == m {
+                break;
+            }
+            let pivot = (rank..m).max_by(|&x, &y| a[(x, c)].modulus().total_cmp(&a[(y, c)].modulus())).unwrap();
+            if a[(pivot, c)].modulus() <= tolerance {
+                continue;
+            }
+            for k in 0..n {
+                let (x, y) = (a[(rank, k)], a[(pivot, k)]);
+                a[(rank, k)] = y;
+                a[(pivot, k)] = x;
+            }
+            for r in rank + 1..m {
+                let factor = a[(r, c)] / a[(rank, c)];
+                for k in c..n {
+                    let u = a[(rank, k)];
+                    a[(r, k)] -= factor * u;
+                }
+            }
+            rank += 1;
+        }
+        rank
+    }
+
+    // Largest entry of A - A†, non square matrices aren't hermitian at all
+    pub fn hermitian_deviation(&self) -> Result<f64, Error> {
+        let (m, n) = self.dim();
+        if m != n {
+            return Err(Error::DimensionMismatch { left: (m, n), right: (n, m) });
+        }
+
+        Ok((0..n).flat_map(|r| (r..n).map(move |c| (r, c)))
+            .map(|(r, c)| (self[(r, c)] - self[(c, r)].conjugate()).modulus())
+            .fold(0.0, f64::max))
+    }
+
+    /// Eigenvalues (ascending) and orthonormal eigenvectors of a hermitian matrix, using cyclic complex Jacobi rotations.
+    pub fn eigenpairs_hermitian(&self) -> Result<Vec<(f64, Vector<C64>)>, Error> {
+        if !self.is_hermitian() {
+            return Err(Error::NotHermitian { deviation: self.hermitian_deviation()? });
+        }
+
+        let n = self.dim().0;
+        // Start from the hermitian part, so round off in the input doesn't leak into the eigenvalues
+        let mut a = ((self.clone() + &self.adjoint()).unwrap()) * C64::new(0.5, 0.0);
+        let mut v = Matrix::<C64>::eye(n);
+
+        let scale = a.data.iter().map(|entry| entry.modulus_squared()).sum::<f64>().sqrt().max(f64::MIN_POSITIVE);
+        for _ in 0..MAX_SWEEPS {
+            let off_diagonal = (0..n).flat_map(|r| (0..n).filter(move |&c| c != r).map(move |c| (r, c)))
+                .map(|(r, c)| a[(r, c)].modulus_squared())
+                .sum::<f64>().sqrt();
+            if off_diagonal <= f64::EPSILON * scale {
+                break;
+            }
+
+            for p in 0..n {
+                for q in p + 1..n {
+                    if a[(p, q)].modulus() > f64::MIN_POSITIVE {
+                        rotate(&mut a, &mut v, p, q);
+                    }
+                }
+            }
+        }
+
+        let mut eigenpairs: Vec<(f64, Vector<C64>)> = (0..n).map(|i| (a[(i, i)].r, v.col(i).unwrap().to_vector())).collect();
+        eigenpairs.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        Ok(eigenpairs)
+    }
+}
+
+// The unitary J with J† [[a, w], [w̄, b]] J diagonal, as (c, s, e^{iφ}) where w = |w| e^{iφ}.
+// J is the phase diag(1, e^{-iφ}) making the block real, followed by the real rotation [[c, s], [-s, c]]
+#[derive(Clone, Copy)]
+struct Rotation {
+    c: C64,
+    s: C64,
+    phase: C64
+}
+
+impl Rotation {
+    fn new(a: f64, b: f64, w: C64) -> Self {
+        // hypot rather than modulus, r * r underflows for tiny w and the phase wouldn't come out unit length
+        let modulus = w.r.hypot(w.i);
+        let theta = 0.5 * (2.0 * modulus).atan2(b - a);
+        let (s, c) = theta.sin_cos();
+        Self { c: C64::new(c, 0.0), s: C64::new(s, 0.0), phase: C64::new(w.r / modulus, w.i / modulus) }
+    }
+
+    // Columns p and q of m times J
+    fn columns(self, m: &mut Matrix<C64>, p: usize, q: usize) {
+        let Rotation { c, s, phase } = self;
+        for k in 0..m.dim().0 {
+            let (x, y) = (m[(k, p)], m[(k, q)]);
+            m[(k, p)] = c * x - s * phase.conjugate() * y;
+            m[(k, q)] = s * x + c * phase.conjugate() * y;
+        }
+    }
+
+    // Rows p and q of m times J†
+    fn rows(self, m: &mut Matrix<C64>, p: usize, q: usize) {
+        let Rotation { c, s, phase } = self;
+        for k in 0..m.dim().1 {
+            let (x, y) = (m[(p, k)], m[(q, k)]);
+            m[(p, k)] = c * x - s * phase * y;
+            m[(q, k)] = s * x + c * phase * y;
+        }
+    }
+}
+
+// Fills column j of u with a unit vector orthogonal to the columns before it.
+// Gram-Schmidt on every standard basis vector, keeping the one that loses the least
+fn complete_column(u: &mut Matrix<C64>, j: usize) {
+    let m = u.dim().0;
+    let columns: Vec<Vector<C64>> = (0..j).map(|previous| u.col(previous).unwrap().to_vector()).collect();
+    let candidate = (0..m)
+        .map(|e| {
+            let mut candidate = Vector::<C64>::zero(m);
+            candidate[e] = C64::ONE;
+            for column in &columns {
+                let projection = column.dot(&candidate).unwrap();
+                candidate -= &(column.clone() * projection);
+            }
+            candidate
+        })
+        .max_by(|a, b| a.norm().total_cmp(&b.norm()))
+        .unwrap();
+
+    let norm = candidate.norm();
+    for r in 0..m {
+        u[(r, j)] = candidate[r] / C64::new(norm, 0.0);
+    }
+}
+
+// Zeroes a[p][q] (and a[q][p]) with A <- J† A J, accumulating V <- V J
+fn rotate(a: &mut Matrix<C64>, v: &mut Matrix<C64>, p: usize, q: usize) {
+    let rotation = Rotation::new(a[(p, p)].r, a[(q, q)].r, a[(p, q)]);
+    rotation.columns(a, p, q);
+    rotation.columns(v, p, q);
+    rotation.rows(a, p, q);
+
+    a[(p, q)] = C64::ZERO;
+    a[(q, p)] = C64::ZERO;
+    a[(p, p)].i = 0.0;
+    a[(q, q)].i = 0.0;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::complex::*;
+    use crate::dynamic::*;
+    use crate::error::Error;
+
+    fn check_eigenpairs(a: &Matrix<C64>, expected: &[f64]) {
+        let eigenpairs = a.eigenpairs_hermitian().unwrap();
+        for (i, (value, vector)) in eigenpairs.iter().enumerate() {
+            assert!((value - expected[i]).abs() < 1e-9, "{value} != {}", expected[i]);
+            let av = (a * vector).unwrap();
+            assert!(av.iter().zip(vector.iter()).all(|(x, y)| (*x - *y * C64::new(*value, 0.0)).modulus() < 1e-9));
+            for (j, (_, other)) in eigenpairs.iter().enumerate() {
+                let expected = if i == j { C64::ONE } else { C64::ZERO };
+                assert!((vector.dot(other).unwrap() - expected).modulus() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_eigenpairs_hermitian() {
+        check_eigenpairs(Gate::pauli_y().get(), &[-1.0, 1.0]);
+        check_eigenpairs(&Matrix::eye(3), &[1.0, 1.0, 1.0]);
+
+        let a = dmat64![[2;0,1;0],
+                        [0,-1;2;1,1],
+                        [0;1,-1;5]];
+        let eigenvalues: Vec<f64> = a.eigenpairs_hermitian().unwrap().iter().map(|(value, _)| *value).collect();
+        check_eigenpairs(&a, &eigenvalues);
+        assert!((eigenvalues.iter().sum::<f64>() - 9.0).abs() < 1e-9);
+
+        // Diagonal in the Bell basis, with eigenvalues 1 (triplet) and -3 (singlet)
+        let heisenberg = [Gate::pauli_x(), Gate::pauli_y(), Gate::pauli_z()].iter()
+            .map(|pauli| pauli.get().tensor_product(pauli.get()))
+            .reduce(|acc, term| (acc + &term).unwrap()).unwrap();
+        check_eigenpairs(&heisenberg, &[-3.0, 1.0, 1.0, 1.0]);
+
+        assert!(matches!(dmat64![[1;1],[0;1]].eigenpairs_hermitian(), Err(Error::NotHermitian { .. })));
+        assert!(matches!(Matrix::<C64>::zeroes(2, 3).eigenpairs_hermitian(), Err(Error::DimensionMismatch { .. })));
+    }
+
+    #[test]
+    fn test_lu() {
+        let a = dmat64![[0;2,1;1],
+                        [1;1;0,-1],
+                        [2;0;3]];
+        let lu = a.lu().unwrap();
+        let pa = Matrix::from_rows(lu.permutation().iter().map(|&r| a.row(r).unwrap().to_vector()), None).unwrap();
+        assert!((&lu.l() * &lu.u()).unwrap().fuzzy_equals(&pa));
+
+        // Cofactor expansion along the first row
+        let det = a.determinant().unwrap();
+        assert!((det - c64!(-6, -7)).modulus() < 1e-9, "{det}");
+
+        let b = dvec64![1;0,1;-2];
+        let x = a.solve(&b).unwrap();
+        assert!((&a * &x).unwrap().fuzzy_equals(&b));
+        assert!((&a * &a.inverse().unwrap()).unwrap().is_identity());
+        assert!(Gate::hadamard().get().inverse().unwrap().fuzzy_equals(Gate::hadamard().get()));
+
+        let singular = dmat64![[1;2;3],[2;4;6],[1;0;1]];
+        assert_eq!(singular.determinant().unwrap(), C64::ZERO);
+        assert_eq!(singular.inverse(), Err(Error::Singular { column: 2 }));
+        assert!(matches!(a.solve(&dvec64![1;2]), Err(Error::DimensionMismatch { .. })));
+        assert!(matches!(Matrix::<C64>::zeroes(2, 3).determinant(), Err(Error::DimensionMismatch { .. })));
+    }
+
+    #[test]
+    fn test_qr_and_rank() {
+        let a = dmat64![[1;2,1],
+                        [0,1;3],
+                        [4;-1,2]];
+        let (q, r) = a.qr();
+        assert!(q.is_unitary());
+        assert!((&q * &r).unwrap().fuzzy_equals(&a));
+        assert!((0..3).all(|row| (0..row.min(2)).all(|col| r[(row, col)] == C64::ZERO)));
+        assert_eq!(a.rank(), 2);
+
+        let (q, r) = a.transpose().qr();
+        assert!((&q * &r).unwrap().fuzzy_equals(&a.transpose()));
+
+        assert_eq!(dmat64![[1;2;3],[2;4;6],[1;0;1]].rank(), 2);
+        assert_eq!(Matrix::<C64>::eye(4).rank(), 4);
+        assert_eq!(Matrix::<C64>::zeroes(3, 2).rank(), 0);
+        assert_eq!(dmat64![[1;2],[2;4],[0;1]].rank(), 2);
+    }
+
+    fn close(a: &Matrix<C64>, b: &Matrix<C64>) -> bool {
+        a.dim() == b.dim() && a.data.iter().zip(&b.data).all(|(x, y)| (*x - *y).modulus() < 1e-9)
+    }
+
+    fn check_svd(a: &Matrix<C64>) -> Vec<f64> {
+        let Svd { u, singular_values, v } = a.svd();
+        let k = a.dim().0.min(a.dim().1);
+        assert_eq!((u.dim(), v.dim(), singular_values.len()), ((a.dim().0, k), (a.dim().1, k), k));
+        assert!(singular_values.windows(2).all(|pair| pair[0] >= pair[1]));
+        assert!(close(&(&u.adjoint() * &u).unwrap(), &Matrix::eye(k)));
+        assert!(close(&(&v.adjoint() * &v).unwrap(), &Matrix::eye(k)));
+
+        let mut sigma = Matrix::zeroes(k, k);
+        for (i, &value) in singular_values.iter().enumerate() {
+            sigma[(i, i)] = C64::new(value, 0.0);
+        }
+        assert!(close(&(&(&u * &sigma).unwrap() * &v.adjoint()).unwrap(), a));
+        singular_values
+    }
+
+    #[test]
+    fn test_svd() {
+        let a = dmat64![[1;2,1;0,-1],
+                        [0,1;3;2],
+                        [4;-1,2;1,1],
+                        [0;1;-2,0.5]];
+        check_svd(&a);
+        check_svd(&a.adjoint());
+
+        let sigma = check_svd(&dmat64![[3;0],[0;-4]]);
+        assert!((sigma[0] - 4.0).abs() < 1e-9 && (sigma[1] - 3.0).abs() < 1e-9);
+
+        // Rank 1, the missing left singular vectors still get filled in
+        let sigma = check_svd(&dmat64![[1;2],[2;4],[3;6]]);
+        assert!((sigma[0] - 70.0_f64.sqrt()).abs() < 1e-9 && sigma[1].abs() < 1e-9);
+        check_svd(&Matrix::zeroes(3, 2));
+    }
+}
@@ -1,12 +1,9 @@
-// use faer_evd::*;
-// use faer_core::Parallelism;
-// use num_complex::Complex;
-
 use crate::complex::*;
+use crate::error::Error;
 use super::vector::*;
 use std::fmt::Display;
 
-use std::ops::{Add, AddAssign, Neg, Sub, SubAssign, Mul, MulAssign};
+use std::ops::{Add, AddAssign, Index, IndexMut, Mul, MulAssign, Neg, Range, Sub, SubAssign};
 
 //These being const make an emulator nearly impossible in Rust, I can't possible know the size of the matrices at compile time.
 // Gonna split this up into a dynamic implementation vs static.
@@ -17,14 +14,14 @@ pub struct Matrix<F: Complex> {
 }
 
 impl<F: Complex> Matrix<F> {
-    pub fn from_rows(iter: impl Iterator<Item = Vector<F>>, rows_hint: Option<usize>) -> Result<Self, &'static str> {
+    pub fn from_rows(iter: impl Iterator<Item = Vector<F>>, rows_hint: Option<usize>) -> Result<Self, Error> {
         let mut row_iter = iter.peekable();
         let (mut m, n) = (0, row_iter.peek().map(|row| row.dim()).unwrap_or(0));
 
         let mut data = Vec::with_capacity(rows_hint.unwrap_or(0) * n);
 
         for row in row_iter {
-            if row.dim() != n { return Err("All provided rows must be the same length."); }
+            if row.dim() != n { return Err(Error::DimensionMismatch { left: (1, n), right: (1, row.dim()) }); }
             m += 1;
 
             for entry in row.iter() {
@@ -61,12 +58,28 @@ impl<F: Complex> Matrix<F> {
         }
     }
 
-    pub fn get(&self, r: usize, c: usize) -> F {
-        self.data[r * self.dim.1 + c]
+    pub fn get(&self, r: usize, c: usize) -> Option<F> {
+        (r < self.dim.0 && c < self.dim.1).then(|| self.data[r * self.dim.1 + c])
     }
 
-    pub fn get_mut(&mut self, r: usize, c: usize) -> &mut F {
-        &mut self.data[r * self.dim.1 + c]
+    pub fn get_mut(&mut self, r: usize, c: usize) -> Option<&mut F> {
+        (r < self.dim.0 && c < self.dim.1).then(|| &mut self.data[r * self.dim.1 + c])
+    }
+
+    pub fn view(&self) -> MatrixView<'_, F> {
+        MatrixView { data: &self.data, stride: self.dim.1, dim: self.dim }
+    }
+
+    pub fn row(&self, r: usize) -> Option<VectorView<'_, F>> {
+        self.view().row(r)
+    }
+
+    pub fn col(&self, c: usize) -> Option<VectorView<'_, F>> {
+        self.view().col(c)
+    }
+
+    pub fn submatrix(&self, rows: Range<usize>, cols: Range<usize>) -> Option<MatrixView<'_, F>> {
+        self.view().submatrix(rows, cols)
     }
 
 
@@ -75,7 +88,7 @@ impl<F: Complex> Matrix<F> {
         let mut data = Vec::with_capacity(n*m);
         for c in 0..n {
             for r in 0..m {
-                data.push(self.get(r,c));
+                data.push(self[(r,c)]);
             }
         }
 
@@ -100,18 +113,6 @@ impl<F: Complex> Matrix<F> {
         self.dim.0 == self.dim.1
     }
 
-    pub fn is_hermitian(&self) -> bool {
-        if !self.is_square() { return false; }
-
-        let mut is_hermitian = true;
-        for r in 0..self.dim.0 {
-            for c in r..self.dim.1 {
-                is_hermitian &= self.get(r,c).conjugate() == self.get(c,r);
-            }
-        }
-        is_hermitian
-    }
-
     pub fn dim(&self) -> (usize, usize) {
         self.dim
     }
@@ -126,7 +127,7 @@ impl<F: Complex> Matrix<F> {
         let mut is_identity = true;
         for r in 0..self.dim().0 {
             for c in 0..self.dim().1 {
-                is_identity &= self.get(r,c).fuzzy_equals(if r == c {
+                is_identity &= self[(r,c)].fuzzy_equals(if r == c {
                     F::ONE
                 } else {
                     F::ZERO
@@ -145,29 +146,37 @@ impl<F: Complex> Matrix<F> {
         is_identity
     }
 
-    pub fn row_iter(&self) -> VecIter<'_, false, F> {
-        VecIter::<'_, false, F> {
-            mat: self,
-            index: 0
-        }
+    pub fn row_iter(&self) -> impl Iterator<Item = VectorView<'_, F>> {
+        (0..self.dim.0).map(|r| self.row(r).unwrap())
     }
 
-    pub fn col_iter(&self) -> VecIter<'_, true, F> {
-        VecIter::<'_, true, F> {
-            mat: self,
-            index: 0
-        }
+    pub fn col_iter(&self) -> impl Iterator<Item = VectorView<'_, F>> {
+        (0..self.dim.1).map(|c| self.col(c).unwrap())
     }
 
-    //TODO: Fix This
+    // For square matrices U U† = I already implies U† U = I
     pub fn is_unitary(&self) -> bool {
         if !self.is_square() { return false; }
 
-        let adj = self.adjoint();
-        let b = (self * &adj).unwrap();
-        let a = (&adj * self).unwrap();
+        (self * &self.adjoint()).unwrap().is_identity()
+    }
 
-        a.is_identity() && b.is_identity()
+    // Straightforward row times column product, kept as a reference for the blocked one in Mul
+    #[cfg(test)]
+    fn mul_naive(&self, rhs: &Self) -> Result<Self, Error> {
+        if self.dim().1 != rhs.dim().0 { return Err(Error::DimensionMismatch { left: self.dim(), right: rhs.dim() }) ;}
+
+        let mut data = Vec::with_capacity(self.dim().0 * rhs.dim().1);
+        for row in self.row_iter() {
+            for col in rhs.col_iter() {
+                data.push(row.iter().zip(col.iter()).fold(F::ZERO, |acc, (&a, &b)| acc + a * b));
+            }
+        }
+
+        Ok(Matrix {
+            dim: (self.dim().0, rhs.dim().1),
+            data
+        })
     }
 
     pub fn tensor_product(&self, rhs: &Self) -> Self {
@@ -180,7 +189,7 @@ impl<F: Complex> Matrix<F> {
             for r2 in 0..m2 {
                 for c1 in 0..n1 {
                     for c2 in 0..n2 {
-                        data.push(self.get(r1,c1) * rhs.get(r2,c2));
+                        data.push(self[(r1,c1)] * rhs[(r2,c2)]);
                     }
                 }
             }
@@ -192,13 +201,18 @@ impl<F: Complex> Matrix<F> {
         }
         
     }
+}
 
-    // Might just implement this myself
-    // pub fn eigenpairs_hermitian(&self) {
-    //     assert!(self.is_hermitian());
+// How far off exact a computed quantity of the given entries can be from round off alone, relative to their norm and number
+pub(crate) fn round_off_tolerance(entries: impl Iterator<Item = C64>, count: usize) -> f64 {
+    entries.map(|entry| entry.modulus_squared()).sum::<f64>().sqrt() * f64::EPSILON * count as f64
+}
 
-    //     let stack_req = compute_hermitian_evd_req(N, ComputeVectors::Yes, Parallelism::None, SymmetricEvdParams::default());
-    // }
+impl Matrix<C64> {
+    // A = A† up to round off, so large entries aren't held to an absolute tolerance
+    pub fn is_hermitian(&self) -> bool {
+        self.hermitian_deviation().is_ok_and(|deviation| deviation <= round_off_tolerance(self.data.iter().copied(), self.dim.0))
+    }
 }
 
 impl<F: Complex> Display for Matrix<F> {
@@ -228,46 +242,93 @@ impl<const M: usize, const N: usize, F: Complex> From<[[F; N];M]> for Matrix<F>
     }
 }
 
-pub struct VecIter<'a, const COL_ORDER: bool,  F: Complex> {
-    mat: &'a Matrix<F>,
-    index: usize
+// Borrowed rectangular block of a matrix, element (r, c) lives at data[r * stride + c]
+#[derive(Clone, Copy, Debug)]
+pub struct MatrixView<'a, F: Complex> {
+    data: &'a [F],
+    stride: usize,
+    dim: (usize, usize)
 }
 
-impl<F:Complex> Iterator for VecIter<'_,false, F> {
-    type Item = Vector<F>;
+impl<'a, F: Complex> MatrixView<'a, F> {
+    pub fn dim(&self) -> (usize, usize) {
+        self.dim
+    }
+
+    pub fn get(&self, r: usize, c: usize) -> Option<F> {
+        (r < self.dim.0 && c < self.dim.1).then(|| self.data[r * self.stride + c])
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let (m, n) = self.mat.dim();
-        if self.index >= m { return None; }
+    // Views with no columns or no rows may have no data behind them, their rows and columns are empty
+    pub fn row(&self, r: usize) -> Option<VectorView<'a, F>> {
+        (r < self.dim.0).then(|| {
+            let data = if self.dim.1 == 0 { &self.data[..0] } else { &self.data[r * self.stride..] };
+            VectorView::new(data, 1, self.dim.1)
+        })
+    }
 
-        let row_vec = Vector::from_iter((0..n).map(|i| self.mat.get(self.index, i)), Some(n));
+    pub fn col(&self, c: usize) -> Option<VectorView<'a, F>> {
+        (c < self.dim.1).then(|| {
+            let data = if self.dim.0 == 0 { &self.data[..0] } else { &self.data[c..] };
+            VectorView::new(data, self.stride, self.dim.0)
+        })
+    }
 
-        self.index += 1;
+    pub fn row_iter(&self) -> impl Iterator<Item = VectorView<'a, F>> + '_ {
+        (0..self.dim.0).map(|r| self.row(r).unwrap())
+    }
 
-        Some(row_vec)
+    pub fn col_iter(&self) -> impl Iterator<Item = VectorView<'a, F>> + '_ {
+        (0..self.dim.1).map(|c| self.col(c).unwrap())
+    }
+
+    pub fn submatrix(&self, rows: Range<usize>, cols: Range<usize>) -> Option<MatrixView<'a, F>> {
+        if rows.start > rows.end || cols.start > cols.end || rows.end > self.dim.0 || cols.end > self.dim.1 {
+            return None;
+        }
+        let dim = (rows.len(), cols.len());
+        let data = if dim.0 == 0 || dim.1 == 0 { &[] } else { &self.data[rows.start * self.stride + cols.start..] };
+        Some(MatrixView { data, stride: self.stride, dim })
+    }
+
+    pub fn to_matrix(&self) -> Matrix<F> {
+        Matrix {
+            dim: self.dim,
+            data: self.row_iter().flat_map(|row| row.iter()).copied().collect()
+        }
     }
 }
 
-impl<F:Complex> Iterator for VecIter<'_,true, F> {
-    type Item = Vector<F>;
+impl<F: Complex> Index<(usize, usize)> for MatrixView<'_, F> {
+    type Output = F;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let (m, n) = self.mat.dim();
-        if self.index >= n { return None; }
+    fn index(&self, (r, c): (usize, usize)) -> &Self::Output {
+        assert!(r < self.dim.0 && c < self.dim.1, "index ({r}, {c}) out of bounds for a {}x{} matrix", self.dim.0, self.dim.1);
+        &self.data[r * self.stride + c]
+    }
+}
 
-        let row_vec = Vector::from_iter((0..m).map(|i| self.mat.get(i, self.index)), Some(m));
+impl<F: Complex> Index<(usize, usize)> for Matrix<F> {
+    type Output = F;
 
-        self.index += 1;
+    fn index(&self, (r, c): (usize, usize)) -> &Self::Output {
+        assert!(r < self.dim.0 && c < self.dim.1, "index ({r}, {c}) out of bounds for a {}x{} matrix", self.dim.0, self.dim.1);
+        &self.data[r * self.dim.1 + c]
+    }
+}
 
-        Some(row_vec)
+impl<F: Complex> IndexMut<(usize, usize)> for Matrix<F> {
+    fn index_mut(&mut self, (r, c): (usize, usize)) -> &mut Self::Output {
+        assert!(r < self.dim.0 && c < self.dim.1, "index ({r}, {c}) out of bounds for a {}x{} matrix", self.dim.0, self.dim.1);
+        &mut self.data[r * self.dim.1 + c]
     }
 }
 
 impl<F: Complex> Add<&Self> for Matrix<F> {
-    type Output = Result<Self, ()>;
+    type Output = Result<Self, Error>;
 
     fn add(mut self, rhs: &Self) -> Self::Output {
-        if self.dim() != rhs.dim() { return Err(()); }
+        if self.dim() != rhs.dim() { return Err(Error::DimensionMismatch { left: self.dim(), right: rhs.dim() }); }
 
         for (i, entry) in self.data.iter_mut().enumerate() {
             entry.add_assign(rhs.data[i]);
@@ -299,10 +360,10 @@ impl<F: Complex> Neg for Matrix<F> {
 }
 
 impl<F: Complex> Sub<&Self> for Matrix<F> {
-    type Output = Result<Self,()>;
+    type Output = Result<Self, Error>;
 
     fn sub(mut self, rhs: &Self) -> Self::Output {
-        if self.dim() != rhs.dim() { return Err(()); }
+        if self.dim() != rhs.dim() { return Err(Error::DimensionMismatch { left: self.dim(), right: rhs.dim() }); }
 
         for (i, entry) in self.data.iter_mut().enumerate() {
             entry.sub_assign(rhs.data[i]);
@@ -343,40 +404,52 @@ impl<F: Complex> MulAssign<F> for Matrix<F> {
 
 //Action on Vectors
 impl<F: Complex> Mul<&Vector<F>> for &Matrix<F>  {
-    type Output = Result<Vector<F>,()>;
+    type Output = Result<Vector<F>, Error>;
 
     fn mul(self, rhs: &Vector<F>) -> Self::Output {
-        if self.dim().1 != rhs.dim() { return Err(()); }
-
-        let row_iter = self.row_iter();
+        if self.dim().1 != rhs.dim() { return Err(Error::DimensionMismatch { left: self.dim(), right: (rhs.dim(), 1) }); }
 
         Ok(Vector::from_iter(
-            row_iter.map(|row| row.dot(rhs).unwrap()), Some(self.dim().0)
+            self.row_iter().map(|row| row.iter().zip(rhs.iter()).fold(F::ZERO, |acc, (&a, &b)| acc + a * b)), Some(self.dim().0)
         ))
     }
 }
 
 //Matrix Multiplication
+// Side length of the square tiles, three 64x64 tiles of C64 come to 192KiB
+const BLOCK_SIZE: usize = 64;
+
 impl<F: Complex> Mul<Self> for &Matrix<F> {
-    type Output = Result<Matrix<F>, ()>;
+    type Output = Result<Matrix<F>, Error>;
 
     fn mul(self, rhs: Self) -> Self::Output {
-        if self.dim().1 != rhs.dim().0 { return Err(()) ;}
-
-        let mut row_iter = self.row_iter();
-        
-        Ok(Matrix::<F>::from_rows(
-            row_iter.map(|row| {
-                Vector::from_iter((0..rhs.dim().1).map(|c| {
-                    let mut sum = F::ZERO;
-                    for i in 0..self.dim().1 {
-                        sum += row.get(i) * rhs.get(i,c)
+        if self.dim().1 != rhs.dim().0 { return Err(Error::DimensionMismatch { left: self.dim(), right: rhs.dim() }) ;}
+
+        let ((m, l), n) = (self.dim(), rhs.dim().1);
+        let mut data = vec![F::ZERO; m * n];
+
+        // Tiles of both operands stay in cache while a tile of the output accumulates, the innermost loop runs along rows of rhs and the output
+        for i0 in (0..m).step_by(BLOCK_SIZE) {
+            for k0 in (0..l).step_by(BLOCK_SIZE) {
+                for j0 in (0..n).step_by(BLOCK_SIZE) {
+                    let (k1, j1) = ((k0 + BLOCK_SIZE).min(l), (j0 + BLOCK_SIZE).min(n));
+                    for i in i0..(i0 + BLOCK_SIZE).min(m) {
+                        let out = &mut data[i * n + j0..i * n + j1];
+                        for k in k0..k1 {
+                            let a = self.data[i * l + k];
+                            for (entry, &b) in out.iter_mut().zip(&rhs.data[k * n + j0..k * n + j1]) {
+                                *entry += a * b;
+                            }
+                        }
                     }
-                    sum
-                }), Some(rhs.dim().1))
-            }), 
-            Some(self.dim().0)
-        ).unwrap())
+                }
+            }
+        }
+
+        Ok(Matrix {
+            dim: (m, n),
+            data
+        })
     }
 }
 
@@ -411,6 +484,36 @@ mod tests {
         assert_eq!((-a.clone() - &a).unwrap(), a * c64!(-2));
     }
 
+    #[test]
+    fn test_views() {
+        let mut a = dmat64![[1;2;3],
+                            [4;5;6]];
+        assert_eq!(a.get(1, 2), Some(c64!(6)));
+        assert_eq!(a.get(2, 0), None);
+        *a.get_mut(0, 0).unwrap() = c64!(7);
+        a[(0, 0)] -= c64!(6);
+        assert!(a.get_mut(0, 3).is_none());
+
+        assert_eq!(a.row(1).unwrap().to_vector(), dvec64![4;5;6]);
+        assert_eq!(a.col(2).unwrap().to_vector(), dvec64![3;6]);
+        assert!(a.col(3).is_none());
+        assert_eq!(a.col(1).unwrap().dot(&a.col(2).unwrap()).unwrap(), c64!(36));
+
+        let block = a.submatrix(0..2, 1..3).unwrap();
+        assert_eq!(block.dim(), (2, 2));
+        assert_eq!(block[(1, 0)], c64!(5));
+        assert_eq!(block.col(1).unwrap().to_vector(), dvec64![3;6]);
+        assert_eq!(block.submatrix(1..2, 0..2).unwrap().to_matrix(), dmat64![[5;6]]);
+        assert_eq!(block.submatrix(0..0, 0..2).unwrap().to_matrix().dim(), (0, 2));
+        assert!(a.submatrix(0..3, 0..1).is_none());
+
+        // Zero rows or columns give empty columns or rows
+        assert_eq!(Matrix::<C64>::zeroes(0, 3).col(1).unwrap().to_vector().dim(), 0);
+        assert_eq!(a.submatrix(0..0, 0..3).unwrap().col(2).unwrap().to_vector().dim(), 0);
+        assert_eq!(a.submatrix(0..2, 0..0).unwrap().row(1).unwrap().to_vector().dim(), 0);
+        assert_eq!(a.submatrix(0..0, 0..3).unwrap().col_iter().count(), 3);
+    }
+
     #[test]
     fn test_matrix_multiplication() {
         let a = dmat64![[3.0, 2.0; 0.0,0.0; 5.0,-6.0],
@@ -424,6 +527,14 @@ mod tests {
                          [9.0, 7.0; 1.0, 29.0; 14.0,0.0],
                          [48.0, -21.0; 15.0, 22.0; 20.0, -22.0]];
         assert_eq!((&a * &b).unwrap(), ab);
+        assert_eq!(a.mul_naive(&b).unwrap(), ab);
+
+        // Sizes that aren't multiples of the block size
+        let entry = |k: usize| C64::new((k % 13) as f64 - 6.0, (k % 7) as f64 - 3.0);
+        let a = Matrix::from_rows((0..70).map(|r| Vector::from_iter((0..130).map(|c| entry(r * 130 + c)), None)), None).unwrap();
+        let b = Matrix::from_rows((0..130).map(|r| Vector::from_iter((0..65).map(|c| entry(3 * r + c)), None)), None).unwrap();
+        assert_eq!((&a * &b).unwrap(), a.mul_naive(&b).unwrap());
+        assert!(matches!(&b * &b, Err(Error::DimensionMismatch { left: (130, 65), right: (130, 65) })));
     }
 
     #[test]
@@ -439,6 +550,12 @@ mod tests {
         assert!(u.is_unitary());
         assert!(!u.is_hermitian());
 
+        // Round off in large entries is tolerated, the same absolute error in small ones isn't
+        let big = dmat64![[3e15; 1e15,2e15],[1e15,-2e15+0.25; -4e15]];
+        assert!(big.is_hermitian());
+        assert!(!dmat64![[3; 1,2],[1,-1.75; -4]].is_hermitian());
+        assert!(!Matrix::<C64>::zeroes(2, 3).is_hermitian());
+
     }
 
     #[test]
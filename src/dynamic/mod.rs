@@ -1,9 +1,5 @@
 // Storing different size vectors and matrices as different types makes it nearly impossible to dynamically construct operators during emulation.
 
-/*
-TODO: Make get methods falible
-*/
-
 #[macro_use]
 mod vector;
 #[macro_use]
@@ -11,8 +7,22 @@ mod matrix;
 
 mod state;
 mod gate;
+mod parallel;
+mod linalg;
+mod hamiltonian;
+mod pauli;
+mod observable;
+mod sparse;
+mod kronecker;
 
 pub use state::*;
 pub use gate::*;
 pub use matrix::*;
-pub use vector::*;
\ No newline at end of file
+pub use vector::*;
+pub use linalg::*;
+pub use hamiltonian::*;
+pub use pauli::*;
+pub use observable::*;
+pub use sparse::*;
+pub use kronecker::*;
+pub use parallel::{num_threads, set_num_threads};
\ No newline at end of file
@@ -0,0 +1,203 @@
+use super::hamiltonian::*;
+use super::matrix::*;
+use super::pauli::*;
+use super::state::*;
+use super::vector::*;
+use crate::complex::*;
+use crate::error::Error;
+
+// Eigenvalues closer than this (relative to the largest one) are taken as one degenerate eigenvalue
+const DEGENERACY_TOLERANCE: f64 = 1e-9;
+
+/// A hermitian operator to measure, its eigendecomposition is computed once on construction.
+#[derive(Clone, Debug)]
+pub struct Observable {
+    matrix: Matrix<C64>,
+    // Distinct eigenvalues (ascending) with an orthonormal basis of their eigenspace
+    eigenspaces: Vec<(f64, Vec<Vector<C64>>)>
+}
+
+impl Observable {
+    pub fn get(&self) -> &Matrix<C64> {
+        &self.matrix
+    }
+
+    pub fn dim(&self) -> usize {
+        self.matrix.dim().0
+    }
+
+    pub fn eigenvalues(&self) -> impl Iterator<Item = f64> + '_ {
+        self.eigenspaces.iter().map(|(eigenvalue, _)| *eigenvalue)
+    }
+
+    fn check_dim(&self, state: &State) -> Result<(), Error> {
+        if state.get().dim() != self.dim() {
+            return Err(Error::DimensionMismatch { left: self.matrix.dim(), right: (state.get().dim(), 1) });
+        }
+        Ok(())
+    }
+
+    /// ⟨ψ|A|ψ⟩
+    pub fn expectation(&self, state: &State) -> Result<f64, Error> {
+        self.check_dim(state)?;
+        let applied = (&self.matrix * state.get())?;
+        Ok(state.get().dot(&applied)?.r)
+    }
+
+    /// ⟨A²⟩ - ⟨A⟩², with ⟨A²⟩ = ‖A|ψ⟩‖² as A is hermitian
+    pub fn variance(&self, state: &State) -> Result<f64, Error> {
+        self.check_dim(state)?;
+        let applied = (&self.matrix * state.get())?;
+        let mean = state.get().dot(&applied)?.r;
+        Ok((applied.norm_squared() - mean * mean).max(0.0))
+    }
+
+    /// Each distinct eigenvalue with the probability of measuring it.
+    pub fn probabilities(&self, state: &State) -> Result<Vec<(f64, f64)>, Error> {
+        self.check_dim(state)?;
+        self.eigenspaces.iter().map(|(eigenvalue, basis)| {
+            let probability = basis.iter().try_fold(0.0, |acc, v| Ok::<_, Error>(acc + v.dot(state.get())?.modulus_squared()))?;
+            Ok((*eigenvalue, probability))
+        }).collect()
+    }
+
+    /// Measures in the eigenbasis, collapsing the state onto the eigenspace of the eigenvalue returned.
+    pub fn measure(&self, state: &mut State) -> Result<f64, Error> {
+        let probabilities: Vec<f64> = self.probabilities(state)?.into_iter().map(|(_, probability)| probability).collect();
+        let (eigenvalue, basis) = &self.eigenspaces[sample(&probabilities)];
+
+        // Projection onto the eigenspace, renormalized
+        let mut projected = Vector::zero(self.dim());
+        for v in basis {
+            projected += &(v.clone() * v.dot(state.get())?);
+        }
+        projected.normalize();
+        state.set(projected);
+        Ok(*eigenvalue)
+    }
+
+    /// Eigenvalues measured on as many fresh copies of the state, the state itself isn't disturbed.
+    pub fn sample(&self, state: &State, shots: usize) -> Result<Vec<f64>, Error> {
+        let probabilities: Vec<f64> = self.probabilities(state)?.into_iter().map(|(_, probability)| probability).collect();
+        Ok((0..shots).map(|_| self.eigenspaces[sample(&probabilities)].0).collect())
+    }
+
+    /// Expectation and (unbiased) variance estimated from the given number of shots, of which there must be at least two.
+    pub fn estimate(&self, state: &State, shots: usize) -> Result<(f64, f64), Error> {
+        if shots < 2 {
+            return Err(Error::TooFewShots { shots, needed: 2 });
+        }
+        let outcomes = self.sample(state, shots)?;
+        let mean = outcomes.iter().sum::<f64>() / shots as f64;
+        let variance = outcomes.iter().map(|outcome| (outcome - mean).powi(2)).sum::<f64>() / (shots - 1) as f64;
+        Ok((mean, variance))
+    }
+}
+
+impl TryFrom<Matrix<C64>> for Observable {
+    type Error = Error;
+    fn try_from(value: Matrix<C64>) -> Result<Self, Self::Error> {
+        if !value.is_hermitian() {
+            return Err(Error::NotHermitian { deviation: value.hermitian_deviation()? });
+        }
+
+        let eigenpairs = value.eigenpairs_hermitian()?;
+        let scale = eigenpairs.iter().map(|(eigenvalue, _)| eigenvalue.abs()).fold(1.0, f64::max);
+        let mut eigenspaces: Vec<(f64, Vec<Vector<C64>>)> = Vec::new();
+        for (eigenvalue, eigenvector) in eigenpairs {
+            match eigenspaces.last_mut() {
+                Some((last, basis)) if eigenvalue - *last < DEGENERACY_TOLERANCE * scale => basis.push(eigenvector),
+                _ => eigenspaces.push((eigenvalue, vec![eigenvector]))
+            }
+        }
+        Ok(Self { matrix: value, eigenspaces })
+    }
+}
+
+impl From<&Hamiltonian> for Observable {
+    fn from(value: &Hamiltonian) -> Self {
+        Self::try_from(value.get().clone()).unwrap()
+    }
+}
+
+impl PauliSum {
+    pub fn to_observable(&self, num_qubits: usize) -> Result<Observable, Error> {
+        Observable::try_from(self.to_matrix(num_qubits)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_1_SQRT_2;
+
+    use crate::complex::*;
+    use crate::dynamic::*;
+    use crate::error::Error;
+
+    #[test]
+    fn test_exact() {
+        // |+⟩ has ⟨Z⟩ = 0 with variance 1, and is an eigenstate of X
+        let plus = State::try_from(dvec64![FRAC_1_SQRT_2; FRAC_1_SQRT_2]).unwrap();
+        let z = Observable::try_from(Gate::pauli_z().get().clone()).unwrap();
+        let x = Observable::try_from(Gate::pauli_x().get().clone()).unwrap();
+        assert!(z.expectation(&plus).unwrap().abs() < 1e-12);
+        assert!((z.variance(&plus).unwrap() - 1.0).abs() < 1e-12);
+        assert!((x.expectation(&plus).unwrap() - 1.0).abs() < 1e-12);
+        assert!(x.variance(&plus).unwrap() < 1e-12);
+        assert_eq!(z.eigenvalues().collect::<Vec<_>>().len(), 2);
+
+        // Agrees with the Pauli expectation that doesn't build a matrix
+        let mut state = State::from_qubits([false, true].into_iter());
+        state.apply(&Gate::hadamard().tensor_product(&Gate::hadamard())).unwrap();
+        state.apply_qubits(&[0, 1], &Gate::cnot()).unwrap();
+        let sum: PauliSum = "0.5*Z0Z1 + 0.3*X0 - 0.2*Y1".parse().unwrap();
+        let observable = sum.to_observable(2).unwrap();
+        assert!((observable.expectation(&state).unwrap() - sum.expectation(&state).unwrap().r).abs() < 1e-12);
+
+        assert!(matches!(Observable::try_from(dmat64![[0;1],[0;0]]), Err(Error::NotHermitian { .. })));
+        assert!(z.expectation(&state).is_err());
+    }
+
+    #[test]
+    fn test_measure() {
+        // Z0Z1 is degenerate, measuring it on a Bell-like superposition keeps the parity subspace coherent
+        let zz = Observable::try_from("Z0Z1".parse::<PauliString>().unwrap().to_matrix(2).unwrap()).unwrap();
+        assert_eq!(zz.eigenvalues().collect::<Vec<_>>(), vec![-1.0, 1.0]);
+
+        let half = C64::new(0.5, 0.0);
+        let mut state = State::try_from(Vector::from(&[half, half, half, half][..])).unwrap();
+        let probabilities = zz.probabilities(&state).unwrap();
+        assert!(probabilities.iter().all(|(_, probability)| (probability - 0.5).abs() < 1e-12));
+
+        let outcome = zz.measure(&mut state).unwrap();
+        let (same, different) = (state.get()[0].modulus_squared() + state.get()[3].modulus_squared(), state.get()[1].modulus_squared() + state.get()[2].modulus_squared());
+        if outcome == 1.0 {
+            assert!((same - 1.0).abs() < 1e-12 && (state.get()[0] - state.get()[3]).modulus() < 1e-12);
+        } else {
+            assert!((different - 1.0).abs() < 1e-12 && (state.get()[1] - state.get()[2]).modulus() < 1e-12);
+        }
+        // Measuring again gives the same eigenvalue, and the state is now an eigenstate
+        assert_eq!(zz.measure(&mut state).unwrap(), outcome);
+        assert!((zz.expectation(&state).unwrap() - outcome).abs() < 1e-12);
+        assert!(zz.variance(&state).unwrap() < 1e-12);
+    }
+
+    #[test]
+    fn test_estimate() {
+        // cos(θ/2)|0⟩ + sin(θ/2)|1⟩ has ⟨Z⟩ = cos θ and variance sin² θ
+        let theta: f64 = 1.1;
+        let state = State::try_from(Vector::from(&[c64!((theta / 2.0).cos()), c64!((theta / 2.0).sin())][..])).unwrap();
+        let z = Observable::try_from(Gate::pauli_z().get().clone()).unwrap();
+
+        let (mean, variance) = z.estimate(&state, 20000).unwrap();
+        // Well over five standard errors
+        assert!((mean - theta.cos()).abs() < 0.04, "{mean}");
+        assert!((variance - theta.sin().powi(2)).abs() < 0.04, "{variance}");
+        assert!(z.sample(&state, 100).unwrap().iter().all(|&outcome| outcome == 1.0 || outcome == -1.0));
+
+        // The unbiased variance needs two shots
+        assert_eq!(z.estimate(&state, 0), Err(Error::TooFewShots { shots: 0, needed: 2 }));
+        assert_eq!(z.estimate(&state, 1), Err(Error::TooFewShots { shots: 1, needed: 2 }));
+        assert!(z.estimate(&state, 2).is_ok());
+    }
+}
@@ -0,0 +1,118 @@
+// Helpers splitting work over slices across scoped threads.
+// Work is always cut into CHUNK_SIZE pieces and per chunk results are combined in chunk order, so the number of threads never changes a result.
+
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+pub(crate) const CHUNK_SIZE: usize = 1 << 12;
+
+// 0 means one thread per available core
+static NUM_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets how many threads the state kernels use, 0 goes back to one per available core.
+pub fn set_num_threads(num_threads: usize) {
+    NUM_THREADS.store(num_threads, Ordering::Relaxed);
+}
+
+pub fn num_threads() -> usize {
+    match NUM_THREADS.load(Ordering::Relaxed) {
+        0 => thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+        n => n
+    }
+}
+
+// Length of the run of chunks each thread gets, covers all of len when there's nothing to split
+fn part_size(len: usize) -> usize {
+    let chunks = len.div_ceil(CHUNK_SIZE);
+    let threads = num_threads().clamp(1, chunks.max(1));
+    chunks.div_ceil(threads).max(1) * CHUNK_SIZE
+}
+
+// f gets the offset of the chunk in data and the chunk, results come back in chunk order
+pub(crate) fn map_chunks<T: Sync, R: Send>(data: &[T], f: impl Fn(usize, &[T]) -> R + Sync) -> Vec<R> {
+    let part_size = part_size(data.len());
+    let run = |offset: usize, part: &[T]| -> Vec<R> {
+        part.chunks(CHUNK_SIZE).enumerate().map(|(i, chunk)| f(offset + i * CHUNK_SIZE, chunk)).collect()
+    };
+
+    if part_size >= data.len() {
+        return run(0, data);
+    }
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = data.chunks(part_size).enumerate()
+            .map(|(i, part)| {
+                let run = &run;
+                scope.spawn(move || run(i * part_size, part))
+            })
+            .collect();
+        handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+    })
+}
+
+pub(crate) fn for_each_chunk_mut<T: Send>(data: &mut [T], f: impl Fn(usize, &mut [T]) + Sync) {
+    let part_size = part_size(data.len());
+    let run = |offset: usize, part: &mut [T]| {
+        for (i, chunk) in part.chunks_mut(CHUNK_SIZE).enumerate() {
+            f(offset + i * CHUNK_SIZE, chunk);
+        }
+    };
+
+    if part_size >= data.len() {
+        return run(0, data);
+    }
+
+    thread::scope(|scope| {
+        for (i, part) in data.chunks_mut(part_size).enumerate() {
+            let run = &run;
+            scope.spawn(move || run(i * part_size, part));
+        }
+    });
+}
+
+// Like for_each_chunk_mut but over the indices 0..len, for work that doesn't map onto contiguous slices
+pub(crate) fn for_each_range(len: usize, f: impl Fn(Range<usize>) + Sync) {
+    let part_size = part_size(len);
+    let run = |part: Range<usize>| {
+        for start in part.clone().step_by(CHUNK_SIZE) {
+            f(start..(start + CHUNK_SIZE).min(part.end));
+        }
+    };
+
+    if part_size >= len {
+        return run(0..len);
+    }
+
+    thread::scope(|scope| {
+        for start in (0..len).step_by(part_size) {
+            let run = &run;
+            scope.spawn(move || run(start..(start + part_size).min(len)));
+        }
+    });
+}
+
+pub(crate) fn sum(values: &[f64]) -> f64 {
+    map_chunks(values, |_, chunk| chunk.iter().sum::<f64>()).into_iter().sum()
+}
+
+// Inclusive prefix sums, chunk totals are scanned first then every chunk adds its offset
+pub(crate) fn prefix_sums(values: &[f64]) -> Vec<f64> {
+    let offsets: Vec<f64> = map_chunks(values, |_, chunk| chunk.iter().sum::<f64>()).into_iter()
+        .scan(0.0, |acc, total| {
+            let offset = *acc;
+            *acc += total;
+            Some(offset)
+        })
+        .collect();
+
+    let mut sums = values.to_vec();
+    for_each_chunk_mut(&mut sums, |offset, chunk| {
+        let mut acc = offsets[offset / CHUNK_SIZE];
+        for value in chunk.iter_mut() {
+            acc += *value;
+            *value = acc;
+        }
+    });
+    sums
+}
@@ -0,0 +1,566 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::ops::{Add, Mul, Neg, Sub};
+use std::str::FromStr;
+
+use super::gate::*;
+use super::hamiltonian::*;
+use super::matrix::*;
+use super::parallel::*;
+use super::state::*;
+use crate::complex::*;
+use crate::error::Error;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum Pauli {
+    I,
+    X,
+    Y,
+    Z
+}
+
+impl Pauli {
+    pub fn matrix(self) -> Matrix<C64> {
+        match self {
+            Pauli::I => Matrix::eye(2),
+            Pauli::X => Gate::pauli_x().get().clone(),
+            Pauli::Y => Gate::pauli_y().get().clone(),
+            Pauli::Z => Gate::pauli_z().get().clone()
+        }
+    }
+
+    // Product as a power of i and a Pauli, e.g. XY = iZ is (1, Z)
+    pub fn product(self, rhs: Self) -> (u8, Self) {
+        match (self, rhs) {
+            (Pauli::I, p) | (p, Pauli::I) => (0, p),
+            (p, q) if p == q => (0, Pauli::I),
+            (Pauli::X, Pauli::Y) => (1, Pauli::Z),
+            (Pauli::Y, Pauli::Z) => (1, Pauli::X),
+            (Pauli::Z, Pauli::X) => (1, Pauli::Y),
+            (Pauli::Y, Pauli::X) => (3, Pauli::Z),
+            (Pauli::Z, Pauli::Y) => (3, Pauli::X),
+            (Pauli::X, Pauli::Z) => (3, Pauli::Y),
+            _ => unreachable!()
+        }
+    }
+}
+
+impl Display for Pauli {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Pauli::I => "I",
+            Pauli::X => "X",
+            Pauli::Y => "Y",
+            Pauli::Z => "Z"
+        })
+    }
+}
+
+// i^power
+fn phase_factor(power: u8) -> C64 {
+    match power % 4 {
+        0 => C64::ONE,
+        1 => C64::new(0.0, 1.0),
+        2 => -C64::ONE,
+        _ => C64::new(0.0, -1.0)
+    }
+}
+
+/// A phase i^k times a tensor product of single qubit Paulis, one per qubit starting at qubit 0. Qubits past the end are acted on by I.
+#[derive(Clone, PartialEq, Eq, Debug, Hash, Default)]
+pub struct PauliString {
+    // Power of i, always below 4
+    phase: u8,
+    paulis: Vec<Pauli>
+}
+
+impl PauliString {
+    pub fn new(mut paulis: Vec<Pauli>) -> Self {
+        // Trailing identities are dropped so equal operators compare equal
+        while paulis.last() == Some(&Pauli::I) {
+            paulis.pop();
+        }
+        Self { phase: 0, paulis }
+    }
+
+    pub fn with_phase(mut self, power: u8) -> Self {
+        self.phase = power % 4;
+        self
+    }
+
+    // The phase as a power of i
+    pub fn phase(&self) -> u8 {
+        self.phase
+    }
+
+    pub fn phase_factor(&self) -> C64 {
+        phase_factor(self.phase)
+    }
+
+    pub fn get(&self, qubit: usize) -> Pauli {
+        self.paulis.get(qubit).copied().unwrap_or(Pauli::I)
+    }
+
+    // The qubits acted on by something other than I, in ascending order
+    pub fn support(&self) -> impl Iterator<Item = (usize, Pauli)> + '_ {
+        self.paulis.iter().copied().enumerate().filter(|(_, pauli)| *pauli != Pauli::I)
+    }
+
+    // Smallest number of qubits the string fits on
+    pub fn num_qubits(&self) -> usize {
+        self.paulis.len()
+    }
+
+    // Hermitian exactly when the phase is ±1
+    pub fn is_hermitian(&self) -> bool {
+        self.phase.is_multiple_of(2)
+    }
+
+    // Two strings anticommute when they differ, both being non identity, on an odd number of qubits
+    pub fn commutes_with(&self, rhs: &Self) -> bool {
+        self.paulis.iter().zip(&rhs.paulis)
+            .filter(|&(&p, &q)| p != Pauli::I && q != Pauli::I && p != q)
+            .count() % 2 == 0
+    }
+
+    pub fn to_matrix(&self, num_qubits: usize) -> Result<Matrix<C64>, Error> {
+        if self.num_qubits() > num_qubits {
+            return Err(Error::TooManyQubits { qubits: self.num_qubits(), num_qubits });
+        }
+        Ok((0..num_qubits).fold(Matrix::eye(1), |acc, qubit| acc.tensor_product(&self.get(qubit).matrix())) * self.phase_factor())
+    }
+
+    /// ⟨ψ|P|ψ⟩ straight from the amplitudes, P|k⟩ is a phase times |k ^ flips⟩ so no matrix is ever built.
+    pub fn expectation(&self, state: &State) -> Result<C64, Error> {
+        let num_qubits = state.num_qubits();
+        if self.num_qubits() > num_qubits {
+            return Err(Error::TooManyQubits { qubits: self.num_qubits(), num_qubits });
+        }
+
+        // Qubit 0 is the most significant bit, X and Y flip a bit, Y and Z give a sign on 1
+        let (mut flips, mut signs, mut num_y) = (0usize, 0usize, 0u8);
+        for (qubit, pauli) in self.support() {
+            let bit = 1 << (num_qubits - 1 - qubit);
+            match pauli {
+                Pauli::X => flips |= bit,
+                Pauli::Y => { flips |= bit; signs |= bit; num_y = (num_y + 1) % 4 },
+                Pauli::Z => signs |= bit,
+                Pauli::I => {}
+            }
+        }
+
+        let amplitudes = &state.get().data;
+        let totals = map_chunks(amplitudes, |offset, chunk| {
+            chunk.iter().enumerate().fold(C64::ZERO, |acc, (i, &amplitude)| {
+                let k = offset + i;
+                let term = amplitudes[k ^ flips].conjugate() * amplitude;
+                if (k & signs).count_ones() % 2 == 0 { acc + term } else { acc - term }
+            })
+        });
+        Ok(totals.into_iter().fold(C64::ZERO, |acc, total| acc + total) * phase_factor((self.phase + num_y) % 4))
+    }
+}
+
+impl Mul for &PauliString {
+    type Output = PauliString;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let num_qubits = self.num_qubits().max(rhs.num_qubits());
+        let (powers, paulis): (Vec<u8>, Vec<Pauli>) = (0..num_qubits).map(|qubit| self.get(qubit).product(rhs.get(qubit))).unzip();
+        // Reduced as it goes, long strings would overflow a plain sum
+        let phase = [self.phase, rhs.phase].into_iter().chain(powers).fold(0u8, |acc, power| (acc + power) % 4);
+        PauliString::new(paulis).with_phase(phase)
+    }
+}
+
+impl Neg for PauliString {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        let phase = self.phase + 2;
+        self.with_phase(phase)
+    }
+}
+
+// Sparse form with the phase in front, e.g. `X0Z2` or `-iY1`, with `I` for the identity
+impl Display for PauliString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(["", "i", "-", "-i"][self.phase as usize])?;
+        if self.paulis.is_empty() {
+            return f.write_str("I");
+        }
+        for (qubit, pauli) in self.support() {
+            write!(f, "{pauli}{qubit}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PauliString {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rest = s.trim();
+        let mut phase = 0;
+        if let Some(stripped) = rest.strip_prefix('-') {
+            (phase, rest) = (2, stripped.trim_start());
+        }
+        if let Some(stripped) = rest.strip_prefix('i') {
+            (phase, rest) = (phase + 1, stripped);
+        }
+
+        let mut paulis = Vec::new();
+        let mut chars = rest.chars().peekable();
+        while let Some(c) = chars.next() {
+            let pauli = match c {
+                'I' => Pauli::I,
+                'X' => Pauli::X,
+                'Y' => Pauli::Y,
+                'Z' => Pauli::Z,
+                _ => return Err(format!("expected one of I, X, Y, Z in {s:?}, found {c:?}"))
+            };
+
+            let mut digits = String::new();
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                digits.push(digit);
+            }
+            if digits.is_empty() {
+                // A lone I is the identity on everything
+                if pauli == Pauli::I {
+                    continue;
+                }
+                return Err(format!("{pauli} in {s:?} is missing its qubit"));
+            }
+
+            let qubit: usize = digits.parse().map_err(|_| format!("qubit {digits} in {s:?} is too large"))?;
+            if paulis.len() <= qubit {
+                paulis.resize(qubit + 1, Pauli::I);
+            }
+            if paulis[qubit] != Pauli::I {
+                return Err(format!("qubit {qubit} appears twice in {s:?}"));
+            }
+            paulis[qubit] = pauli;
+        }
+        Ok(Self::new(paulis).with_phase(phase))
+    }
+}
+
+/// Complex weighted sum of Pauli strings such as `0.5*Z0Z1 + 0.3*X0`, hermitian when every coefficient is real.
+/// The strings are kept without a phase, pushing one folds its phase into the coefficient.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct PauliSum {
+    terms: Vec<(C64, PauliString)>
+}
+
+impl PauliSum {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, coefficient: impl Into<C64>, string: PauliString) {
+        let coefficient = coefficient.into() * string.phase_factor();
+        self.terms.push((coefficient, string.with_phase(0)));
+    }
+
+    pub fn terms(&self) -> &[(C64, PauliString)] {
+        &self.terms
+    }
+
+    pub fn num_qubits(&self) -> usize {
+        self.terms.iter().map(|(_, string)| string.num_qubits()).max().unwrap_or(0)
+    }
+
+    /// Merges repeated strings, keeping the order they first appear in, and drops terms that cancel to zero.
+    pub fn simplify(&self) -> Self {
+        let mut index: HashMap<PauliString, usize> = HashMap::new();
+        let mut terms: Vec<(C64, PauliString)> = Vec::new();
+        for (coefficient, string) in &self.terms {
+            match index.get(string) {
+                Some(&i) => terms[i].0 += *coefficient,
+                None => {
+                    index.insert(string.clone(), terms.len());
+                    terms.push((*coefficient, string.clone()));
+                }
+            }
+        }
+        terms.retain(|(coefficient, _)| *coefficient != C64::ZERO);
+        Self { terms }
+    }
+
+    // Largest entry of 2 Im(c) over the simplified terms, the weight of each string in A - A†
+    pub fn hermitian_deviation(&self) -> f64 {
+        self.simplify().terms.iter().map(|(coefficient, _)| 2.0 * coefficient.i.abs()).fold(0.0, f64::max)
+    }
+
+    // Real coefficients up to round off, with the same tolerance as Matrix::is_hermitian
+    pub fn is_hermitian(&self) -> bool {
+        let simplified = self.simplify();
+        simplified.hermitian_deviation() <= round_off_tolerance(simplified.terms.iter().map(|(coefficient, _)| *coefficient), simplified.terms.len())
+    }
+
+    pub fn to_matrix(&self, num_qubits: usize) -> Result<Matrix<C64>, Error> {
+        self.terms.iter().try_fold(Matrix::zeroes(1 << num_qubits, 1 << num_qubits), |acc, (coefficient, string)| {
+            acc + &(string.to_matrix(num_qubits)? * *coefficient)
+        })
+    }
+
+    pub fn to_hamiltonian(&self, num_qubits: usize) -> Result<Hamiltonian, Error> {
+        Hamiltonian::try_from(self.to_matrix(num_qubits)?)
+    }
+
+    // Sum of the terms' expectation values, each computed without building a matrix
+    pub fn expectation(&self, state: &State) -> Result<C64, Error> {
+        self.terms.iter().try_fold(C64::ZERO, |acc, (coefficient, string)| Ok(acc + *coefficient * string.expectation(state)?))
+    }
+}
+
+impl Add<&Self> for PauliSum {
+    type Output = Self;
+
+    fn add(mut self, rhs: &Self) -> Self::Output {
+        self.terms.extend(rhs.terms.iter().cloned());
+        self.simplify()
+    }
+}
+
+impl Sub<&Self> for PauliSum {
+    type Output = Self;
+
+    fn sub(self, rhs: &Self) -> Self::Output {
+        self + &(rhs.clone() * -C64::ONE)
+    }
+}
+
+impl Mul<C64> for PauliSum {
+    type Output = Self;
+
+    fn mul(mut self, rhs: C64) -> Self::Output {
+        for (coefficient, _) in &mut self.terms {
+            *coefficient *= rhs;
+        }
+        self
+    }
+}
+
+impl Mul for &PauliSum {
+    type Output = PauliSum;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut product = PauliSum::new();
+        for (a, p) in &self.terms {
+            for (b, q) in &rhs.terms {
+                product.push(*a * *b, p * q);
+            }
+        }
+        product.simplify()
+    }
+}
+
+// Real coefficients print as they are, others as (re±imi)
+fn write_coefficient(f: &mut std::fmt::Formatter<'_>, coefficient: C64) -> std::fmt::Result {
+    if coefficient.i == 0.0 {
+        write!(f, "{}", coefficient.r)
+    } else if coefficient.i.is_sign_negative() {
+        write!(f, "({}-{}i)", coefficient.r, -coefficient.i)
+    } else {
+        write!(f, "({}+{}i)", coefficient.r, coefficient.i)
+    }
+}
+
+impl Display for PauliSum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.terms.is_empty() {
+            return f.write_str("0");
+        }
+        for (i, (coefficient, string)) in self.terms.iter().enumerate() {
+            // A real negative coefficient becomes a minus between terms
+            let coefficient = match (i, coefficient.i == 0.0 && coefficient.r.is_sign_negative()) {
+                (0, _) => *coefficient,
+                (_, false) => { f.write_str(" + ")?; *coefficient },
+                (_, true) => { f.write_str(" - ")?; -*coefficient }
+            };
+            write_coefficient(f, coefficient)?;
+            write!(f, "*{string}")?;
+        }
+        Ok(())
+    }
+}
+
+// A real number, or re±imi in parentheses
+fn parse_coefficient(s: &str) -> Result<C64, String> {
+    let error = || format!("invalid coefficient {s:?}");
+    let Some(inner) = s.strip_prefix('(').and_then(|s| s.strip_suffix(')')) else {
+        return s.parse().map(|r: f64| C64::new(r, 0.0)).map_err(|_| error());
+    };
+
+    let inner = inner.trim();
+    let imaginary = inner.strip_suffix('i').ok_or_else(error)?;
+    // The sign splitting the parts is the last one not after an exponent's e or at the very start
+    let split = imaginary.char_indices()
+        .rfind(|&(i, c)| i > 0 && matches!(c, '+' | '-') && !imaginary[..i].ends_with(['e', 'E']))
+        .map_or(0, |(i, _)| i);
+    let (r, i) = imaginary.split_at(split);
+    let r = if r.trim().is_empty() { 0.0 } else { r.trim().parse().map_err(|_| error())? };
+    let i = match i.trim() {
+        "" | "+" => 1.0,
+        "-" => -1.0,
+        i => i.replace(' ', "").parse().map_err(|_| error())?
+    };
+    Ok(C64::new(r, i))
+}
+
+// Terms joined by + or -, each an optional signed `coefficient*` and a Pauli string
+impl FromStr for PauliSum {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut sum = PauliSum::new();
+        let mut rest = s;
+        loop {
+            // Any signs in front of the term, the one joining it to the last term included
+            let mut sign = 1.0;
+            rest = rest.trim_start();
+            while let Some(stripped) = rest.strip_prefix(['+', '-']) {
+                if rest.starts_with('-') {
+                    sign = -sign;
+                }
+                rest = stripped.trim_start();
+            }
+
+            // Signs right after an exponent's e or inside a complex coefficient belong to the coefficient
+            let mut depth = 0;
+            let end = rest.char_indices()
+                .find(|&(i, c)| {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        _ => {}
+                    }
+                    depth == 0 && matches!(c, '+' | '-') && !rest[..i].ends_with(['e', 'E'])
+                })
+                .map_or(rest.len(), |(i, _)| i);
+            let term = rest[..end].trim();
+            let (coefficient, string) = match term.split_once('*') {
+                Some((coefficient, string)) => (parse_coefficient(coefficient.trim())?, string),
+                None => (C64::ONE, term)
+            };
+            if string.trim().is_empty() {
+                return Err(format!("missing Pauli string in {s:?}"));
+            }
+            sum.push(coefficient * C64::new(sign, 0.0), string.parse()?);
+
+            if end == rest.len() {
+                break;
+            }
+            rest = &rest[end..];
+        }
+        Ok(sum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::complex::*;
+    use crate::dynamic::*;
+    use crate::error::Error;
+
+    #[test]
+    fn test_parse() {
+        let sum: PauliSum = "0.5*Z0Z1 + 0.3*X0 - Y2 + 2*I".parse().unwrap();
+        assert_eq!(sum.num_qubits(), 3);
+        assert_eq!(sum.terms()[0], (c64!(0.5), PauliString::new(vec![Pauli::Z, Pauli::Z])));
+        assert_eq!(sum.terms()[2], (c64!(-1.0), PauliString::new(vec![Pauli::I, Pauli::I, Pauli::Y])));
+        assert_eq!(sum.terms()[3].1, PauliString::default());
+        assert_eq!(sum.to_string(), "0.5*Z0Z1 + 0.3*X0 - 1*Y2 + 2*I");
+        assert_eq!(sum.to_string().parse::<PauliSum>().unwrap(), sum);
+        assert_eq!("-X3".parse::<PauliSum>().unwrap().terms()[0].0, c64!(-1.0));
+        assert_eq!("1e-3*X0 - 2.5E+1*Z1".parse::<PauliSum>().unwrap().terms().iter().map(|(c, _)| *c).collect::<Vec<_>>(), vec![c64!(1e-3), c64!(-25.0)]);
+        assert_eq!("Z0 + -0.5*X1 - -2*Y0".parse::<PauliSum>().unwrap().terms().iter().map(|(c, _)| *c).collect::<Vec<_>>(), vec![c64!(1), c64!(-0.5), c64!(2)]);
+
+        // Complex coefficients, and phases on strings folded into them
+        let sum: PauliSum = "(0.5-2i)*X0 - (1e-3+i)*Z1 + iY0 + (0-1i)*Z0".parse().unwrap();
+        assert_eq!(sum.terms().iter().map(|(c, _)| *c).collect::<Vec<_>>(), vec![c64!(0.5, -2), c64!(-1e-3, -1), c64!(0, 1), c64!(0, -1)]);
+        assert_eq!(sum.to_string(), "(0.5-2i)*X0 + (-0.001-1i)*Z1 + (0+1i)*Y0 + (0-1i)*Z0");
+        assert_eq!(sum.to_string().parse::<PauliSum>().unwrap(), sum);
+        assert_eq!("-iX0Y1".parse::<PauliString>().unwrap().to_string(), "-iX0Y1");
+
+        assert!("0.5*Z0Z0".parse::<PauliSum>().is_err());
+        assert!("0.5*Q0".parse::<PauliSum>().is_err());
+        assert!("0.5*Z".parse::<PauliSum>().is_err());
+        assert!("0.5*Z0 +".parse::<PauliSum>().is_err());
+        assert!("(0.5+2)*Z0".parse::<PauliSum>().is_err());
+    }
+
+    #[test]
+    fn test_algebra() {
+        let string = |s: &str| s.parse::<PauliString>().unwrap();
+        assert_eq!(&string("X0") * &string("Y0"), string("iZ0"));
+        assert_eq!(&string("Y0") * &string("X0"), string("-iZ0"));
+        assert_eq!(&string("X0Z1") * &string("X0Z1"), string("I"));
+        assert_eq!(&string("iX0Y1") * &string("Z0Z1Y2"), string("iY0X1Y2"));
+
+        // (YX)^100 = (-iZ)^100, far more phase powers than fit in a u8
+        let long = |pauli: char, n: usize| string(&(0..n).map(|qubit| format!("{pauli}{qubit}")).collect::<String>());
+        assert_eq!(&long('Y', 100) * &long('X', 100), long('Z', 100));
+        assert_eq!(&long('Y', 101) * &long('X', 101), string(&format!("-i{}", long('Z', 101))));
+
+        // Products agree with the matrices
+        for (p, q) in [("X0Y1", "Z0Z1"), ("-Y0", "iX0Z2"), ("Z1", "Y0X1")] {
+            let (p, q) = (string(p), string(q));
+            assert_eq!((&p.to_matrix(3).unwrap() * &q.to_matrix(3).unwrap()).unwrap(), (&p * &q).to_matrix(3).unwrap());
+        }
+
+        assert!(string("X0X1").commutes_with(&string("Z0Z1")));
+        assert!(!string("X0").commutes_with(&string("Z0Z1")));
+        assert!(string("X0").commutes_with(&string("Z1")));
+        assert!(string("-X0").is_hermitian() && !string("iX0").is_hermitian());
+
+        // [X, Y] = 2iZ, and (X + Y)² = 2 since they anticommute
+        let (x, y): (PauliSum, PauliSum) = ("X0".parse().unwrap(), "Y0".parse().unwrap());
+        assert_eq!((&x * &y) - &(&y * &x), "(0+2i)*Z0".parse().unwrap());
+        let sum = x.clone() + &y;
+        assert_eq!(&sum * &sum, "2*I".parse().unwrap());
+        assert_eq!((x.clone() - &x).terms().len(), 0);
+        assert_eq!((x * C64::new(0.0, 1.0)).hermitian_deviation(), 2.0);
+    }
+
+    #[test]
+    fn test_expectation() {
+        // Random looking 3 qubit state
+        let amplitudes = Vector::from(&[c64!(0.1, 0.2), c64!(0.3, -0.1), c64!(-0.2, 0.4), c64!(0.1), c64!(0.5, 0.1), c64!(-0.3, -0.2), c64!(0.2, 0.1), c64!(0.0, 0.3)][..]);
+        let norm = amplitudes.dot(&amplitudes).unwrap().r.sqrt();
+        let state = State::try_from(amplitudes * C64::new(1.0 / norm, 0.0)).unwrap();
+        let dense = |matrix: &Matrix<C64>| state.get().dot(&(matrix * state.get()).unwrap()).unwrap();
+
+        for s in ["I", "X0", "Y1", "Z2", "X0Y1Z2", "iY0Y2", "-Z0X1"] {
+            let string: PauliString = s.parse().unwrap();
+            let expected = dense(&string.to_matrix(3).unwrap());
+            let got = string.expectation(&state).unwrap();
+            assert!((got - expected).modulus() < 1e-12, "{s}: {got} against {expected}");
+        }
+
+        let sum: PauliSum = "0.5*Z0Z1 + 0.3*X0 - 0.7*Y1Y2".parse().unwrap();
+        assert!((sum.expectation(&state).unwrap() - dense(&sum.to_matrix(3).unwrap())).modulus() < 1e-12);
+
+        assert_eq!("Z3".parse::<PauliString>().unwrap().expectation(&state), Err(Error::TooManyQubits { qubits: 4, num_qubits: 3 }));
+        assert_eq!("Z70".parse::<PauliString>().unwrap().expectation(&state), Err(Error::TooManyQubits { qubits: 71, num_qubits: 3 }));
+    }
+
+    #[test]
+    fn test_to_matrix() {
+        let zz: PauliString = "Z0Z1".parse().unwrap();
+        assert_eq!(zz.to_matrix(2).unwrap(), dmat64![[1;0;0;0],[0;-1;0;0],[0;0;-1;0],[0;0;0;1]]);
+        // Qubit 0 is the most significant
+        assert_eq!("X1".parse::<PauliString>().unwrap().to_matrix(2).unwrap(), Matrix::eye(2).tensor_product(Gate::pauli_x().get()));
+
+        let sum: PauliSum = "0.5*Z0Z1 + 0.3*X0".parse().unwrap();
+        assert!(sum.to_hamiltonian(2).is_ok());
+        assert_eq!(sum.to_matrix(2).unwrap()[(0, 0)], c64!(0.5));
+        assert_eq!(sum.to_matrix(2).unwrap()[(2, 0)], c64!(0.3));
+
+        // Paulis past the last qubit are errors rather than dropped
+        assert_eq!("Z3".parse::<PauliString>().unwrap().to_matrix(2), Err(Error::TooManyQubits { qubits: 4, num_qubits: 2 }));
+        assert_eq!(sum.to_matrix(1), Err(Error::TooManyQubits { qubits: 2, num_qubits: 1 }));
+        assert_eq!(sum.to_hamiltonian(1).err(), Some(Error::TooManyQubits { qubits: 2, num_qubits: 1 }));
+    }
+}
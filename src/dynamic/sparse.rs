@@ -0,0 +1,329 @@
+use crate::complex::*;
+use crate::error::Error;
+use super::matrix::*;
+use super::vector::*;
+
+use std::ops::Mul;
+
+/// Compressed sparse row matrix, only the nonzero entries are stored.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SparseMatrix<F: Complex> {
+    dim: (usize, usize),
+    // Entries of row r are at row_offsets[r]..row_offsets[r + 1]
+    row_offsets: Vec<usize>,
+    // Ascending within each row
+    columns: Vec<usize>,
+    values: Vec<F>
+}
+
+impl<F: Complex> SparseMatrix<F> {
+    /// Builds from (row, column, value) triplets in any order, repeated positions are summed.
+    pub fn from_triplets(m: usize, n: usize, triplets: impl IntoIterator<Item = (usize, usize, F)>) -> Result<Self, Error> {
+        let mut triplets: Vec<(usize, usize, F)> = triplets.into_iter().collect();
+        if let Some(&(r, c, _)) = triplets.iter().find(|&&(r, c, _)| r >= m || c >= n) {
+            return Err(Error::IndexOutOfBounds { index: (r, c), dim: (m, n) });
+        }
+        triplets.sort_by_key(|&(r, c, _)| (r, c));
+
+        let mut merged: Vec<(usize, usize, F)> = Vec::with_capacity(triplets.len());
+        for (r, c, value) in triplets {
+            match merged.last_mut() {
+                Some(last) if (last.0, last.1) == (r, c) => last.2 += value,
+                _ => merged.push((r, c, value))
+            }
+        }
+        merged.retain(|&(_, _, value)| value != F::ZERO);
+
+        let mut row_offsets = vec![0; m + 1];
+        for &(r, _, _) in &merged {
+            row_offsets[r + 1] += 1;
+        }
+        for r in 0..m {
+            row_offsets[r + 1] += row_offsets[r];
+        }
+
+        Ok(Self {
+            dim: (m, n),
+            row_offsets,
+            columns: merged.iter().map(|&(_, c, _)| c).collect(),
+            values: merged.iter().map(|&(_, _, value)| value).collect()
+        })
+    }
+
+    pub fn zeroes(m: usize, n: usize) -> Self {
+        Self { dim: (m, n), row_offsets: vec![0; m + 1], columns: Vec::new(), values: Vec::new() }
+    }
+
+    pub fn eye(n: usize) -> Self {
+        Self { dim: (n, n), row_offsets: (0..=n).collect(), columns: (0..n).collect(), values: vec![F::ONE; n] }
+    }
+
+    pub fn dim(&self) -> (usize, usize) {
+        self.dim
+    }
+
+    pub fn is_square(&self) -> bool {
+        self.dim.0 == self.dim.1
+    }
+
+    // Number of stored entries
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    // None out of bounds, zero for entries that aren't stored
+    pub fn get(&self, r: usize, c: usize) -> Option<F> {
+        if r >= self.dim.0 || c >= self.dim.1 { return None; }
+        let range = self.row_offsets[r]..self.row_offsets[r + 1];
+        Some(match self.columns[range.clone()].binary_search(&c) {
+            Ok(i) => self.values[range.start + i],
+            Err(_) => F::ZERO
+        })
+    }
+
+    // The (column, value) entries of a row, by ascending column, None out of bounds
+    pub fn row(&self, r: usize) -> Option<impl Iterator<Item = (usize, F)> + '_> {
+        (r < self.dim.0).then(|| self.entries(r))
+    }
+
+    // row without the bounds check, for rows known to exist
+    fn entries(&self, r: usize) -> impl Iterator<Item = (usize, F)> + '_ {
+        let range = self.row_offsets[r]..self.row_offsets[r + 1];
+        self.columns[range.clone()].iter().copied().zip(self.values[range].iter().copied())
+    }
+
+    // Back to (row, column, value) triplets, row by row
+    pub fn triplets(&self) -> impl Iterator<Item = (usize, usize, F)> + '_ {
+        (0..self.dim.0).flat_map(move |r| self.entries(r).map(move |(c, value)| (r, c, value)))
+    }
+
+    pub fn to_dense(&self) -> Matrix<F> {
+        let mut dense = Matrix::zeroes(self.dim.0, self.dim.1);
+        for (r, c, value) in self.triplets() {
+            dense[(r, c)] = value;
+        }
+        dense
+    }
+
+    pub fn transpose(&self) -> Self {
+        let (m, n) = self.dim;
+        // Counting sort of the entries by column, rows come out ascending as they're visited in order
+        let mut row_offsets = vec![0; n + 1];
+        for &c in &self.columns {
+            row_offsets[c + 1] += 1;
+        }
+        for c in 0..n {
+            row_offsets[c + 1] += row_offsets[c];
+        }
+
+        let mut next = row_offsets.clone();
+        let mut columns = vec![0; self.nnz()];
+        let mut values = vec![F::ZERO; self.nnz()];
+        for (r, c, value) in self.triplets() {
+            columns[next[c]] = r;
+            values[next[c]] = value;
+            next[c] += 1;
+        }
+
+        Self { dim: (n, m), row_offsets, columns, values }
+    }
+
+    pub fn conjugate(mut self) -> Self {
+        for value in self.values.iter_mut() {
+            *value = value.conjugate();
+        }
+        self
+    }
+
+    pub fn adjoint(&self) -> Self {
+        self.transpose().conjugate()
+    }
+
+    pub fn tensor_product(&self, rhs: &Self) -> Self {
+        let (m1, n1) = self.dim;
+        let (m2, n2) = rhs.dim;
+
+        let mut row_offsets = Vec::with_capacity(m1 * m2 + 1);
+        let mut columns = Vec::with_capacity(self.nnz() * rhs.nnz());
+        let mut values = Vec::with_capacity(self.nnz() * rhs.nnz());
+        row_offsets.push(0);
+        for r1 in 0..m1 {
+            for r2 in 0..m2 {
+                for (c1, a) in self.entries(r1) {
+                    for (c2, b) in rhs.entries(r2) {
+                        columns.push(c1 * n2 + c2);
+                        values.push(a * b);
+                    }
+                }
+                row_offsets.push(columns.len());
+            }
+        }
+
+        Self { dim: (m1 * m2, n1 * n2), row_offsets, columns, values }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        if !self.is_square() { return false; }
+
+        // Every diagonal entry has to be stored and about one, everything else about zero
+        (0..self.dim.0).all(|r| self.entries(r).any(|(c, _)| c == r))
+            && self.triplets().all(|(r, c, value)| value.fuzzy_equals(if r == c { F::ONE } else { F::ZERO }))
+    }
+
+    // U U† stays sparse for the usual sparse unitaries (permutations, controlled gates), so nothing is ever densified
+    pub fn is_unitary(&self) -> bool {
+        if !self.is_square() { return false; }
+
+        (self * &self.adjoint()).unwrap().is_identity()
+    }
+}
+
+impl<F: Complex> From<&Matrix<F>> for SparseMatrix<F> {
+    fn from(value: &Matrix<F>) -> Self {
+        let (m, n) = value.dim();
+        let triplets = (0..m).flat_map(|r| (0..n).map(move |c| (r, c, value[(r, c)])));
+        Self::from_triplets(m, n, triplets).unwrap()
+    }
+}
+
+impl<F: Complex> Mul<F> for SparseMatrix<F> {
+    type Output = Self;
+    fn mul(mut self, rhs: F) -> Self::Output {
+        for value in self.values.iter_mut() {
+            value.mul_assign(rhs);
+        }
+        self
+    }
+}
+
+//Action on Vectors
+impl<F: Complex> Mul<&Vector<F>> for &SparseMatrix<F> {
+    type Output = Result<Vector<F>, Error>;
+
+    fn mul(self, rhs: &Vector<F>) -> Self::Output {
+        if self.dim().1 != rhs.dim() { return Err(Error::DimensionMismatch { left: self.dim(), right: (rhs.dim(), 1) }); }
+
+        let mut result = Vector::zero(self.dim.0);
+        for r in 0..self.dim.0 {
+            for (c, value) in self.entries(r) {
+                result[r] += value * rhs[c];
+            }
+        }
+        Ok(result)
+    }
+}
+
+//Matrix Multiplication
+impl<F: Complex> Mul<Self> for &SparseMatrix<F> {
+    type Output = Result<SparseMatrix<F>, Error>;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        if self.dim().1 != rhs.dim().0 { return Err(Error::DimensionMismatch { left: self.dim(), right: rhs.dim() }); }
+
+        let (m, n) = (self.dim.0, rhs.dim.1);
+        let mut row_offsets = Vec::with_capacity(m + 1);
+        let mut columns = Vec::new();
+        let mut values = Vec::new();
+        row_offsets.push(0);
+
+        // Row by row (Gustavson), scattering into a dense accumulator and remembering which columns were touched
+        let mut accumulator = vec![F::ZERO; n];
+        let mut touched = vec![false; n];
+        let mut row_columns = Vec::new();
+        for r in 0..m {
+            for (k, a) in self.entries(r) {
+                for (c, b) in rhs.entries(k) {
+                    if !touched[c] {
+                        touched[c] = true;
+                        row_columns.push(c);
+                    }
+                    accumulator[c] += a * b;
+                }
+            }
+
+            row_columns.sort_unstable();
+            for &c in &row_columns {
+                if accumulator[c] != F::ZERO {
+                    columns.push(c);
+                    values.push(accumulator[c]);
+                }
+                accumulator[c] = F::ZERO;
+                touched[c] = false;
+            }
+            row_columns.clear();
+            row_offsets.push(columns.len());
+        }
+
+        Ok(SparseMatrix { dim: (m, n), row_offsets, columns, values })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::complex::*;
+    use crate::dynamic::*;
+    use crate::error::Error;
+
+    fn example() -> Matrix<C64> {
+        dmat64![[0;2,1;0],[0;0;0],[-1;0;0,-3],[0;0;5]]
+    }
+
+    #[test]
+    fn test_construction() {
+        let sparse = SparseMatrix::from_triplets(2, 3, [(1, 2, c64!(1)), (0, 0, c64!(2)), (1, 2, c64!(0, 1)), (0, 1, c64!(3)), (0, 1, c64!(-3))]).unwrap();
+        // Repeats are summed and cancelled entries dropped
+        assert_eq!(sparse.nnz(), 2);
+        assert_eq!(sparse.get(1, 2), Some(c64!(1, 1)));
+        assert_eq!(sparse.get(0, 1), Some(C64::ZERO));
+        assert_eq!(sparse.get(2, 0), None);
+        assert_eq!(sparse.row(1).unwrap().collect::<Vec<_>>(), vec![(2, c64!(1, 1))]);
+        assert!(sparse.row(2).is_none());
+        assert_eq!(sparse.triplets().collect::<Vec<_>>(), vec![(0, 0, c64!(2)), (1, 2, c64!(1, 1))]);
+
+        assert_eq!(SparseMatrix::from_triplets(2, 2, [(0, 2, c64!(1))]), Err(Error::IndexOutOfBounds { index: (0, 2), dim: (2, 2) }));
+
+        let dense = example();
+        let sparse = SparseMatrix::from(&dense);
+        assert_eq!(sparse.nnz(), 4);
+        assert_eq!(sparse.to_dense(), dense);
+        assert_eq!(sparse.adjoint().to_dense(), dense.adjoint());
+        assert_eq!(SparseMatrix::<C64>::eye(3).to_dense(), Matrix::eye(3));
+    }
+
+    #[test]
+    fn test_products() {
+        let dense = example();
+        let sparse = SparseMatrix::from(&dense);
+
+        let v = dvec64![1;0,1;-2];
+        assert_eq!((&sparse * &v).unwrap(), (&dense * &v).unwrap());
+        assert!((&sparse * &dvec64![1;2]).is_err());
+
+        let other = dmat64![[1;0;0;2],[0;0,-1;0;0],[3;0;1;0]];
+        assert_eq!((&sparse * &SparseMatrix::from(&other)).unwrap().to_dense(), (&dense * &other).unwrap());
+        assert_eq!((&sparse * &sparse.adjoint()).unwrap().to_dense(), (&dense * &dense.adjoint()).unwrap());
+        assert!((&sparse * &sparse).is_err());
+
+        let small = dmat64![[0;1],[2,1;0]];
+        assert_eq!(sparse.tensor_product(&SparseMatrix::from(&small)).to_dense(), dense.tensor_product(&small));
+        assert_eq!(SparseMatrix::from(&small).tensor_product(&sparse).to_dense(), small.tensor_product(&dense));
+    }
+
+    #[test]
+    fn test_is_unitary() {
+        let cnot = SparseMatrix::from(Gate::cnot().get());
+        assert!(cnot.is_unitary());
+        assert!(!SparseMatrix::from(&example()).is_unitary());
+        assert!(!(SparseMatrix::<C64>::eye(4) * c64!(2)).is_unitary());
+
+        // |x⟩ -> |x + 1 mod 2^16⟩ as a phase-twisted permutation, far too large to check densely
+        let n = 1 << 16;
+        let shift = SparseMatrix::from_triplets(n, n, (0..n).map(|x| ((x + 1) % n, x, C64::nth_root_of_unity(n).pow(x)))).unwrap();
+        assert!(shift.is_unitary());
+        assert!(shift.tensor_product(&SparseMatrix::from(Gate::hadamard().get())).is_unitary());
+
+        // Missing a diagonal entry isn't the identity
+        let projector = SparseMatrix::from_triplets(2, 2, [(0, 0, c64!(1))]).unwrap();
+        assert!(!projector.is_unitary());
+    }
+}
@@ -1,10 +1,15 @@
 use super::vector::*;
 use crate::complex::*;
+use crate::error::Error;
 use super::gate::*;
+use super::kronecker::*;
+use super::matrix::*;
+use super::linalg::Svd;
+use super::parallel::*;
 use std::{ops::Range, random::random};
 
 // At this point keep state invariants (normalized, etc..)
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct State(Vector<C64>);
 
 
@@ -13,6 +18,11 @@ impl State {
         &self.0
     }
 
+    // Replaces the amplitudes without the normalization check, the caller keeps them normalized
+    pub(super) fn set(&mut self, amplitudes: Vector<C64>) {
+        self.0 = amplitudes;
+    }
+
     pub fn from_qubit(enabled: bool) -> Self {
         Self(
             Vector::from(if enabled {
@@ -29,26 +39,34 @@ impl State {
         )    
     }
 
-    pub fn apply(&mut self, op: &Gate) {
-        if op.get().dim().0 != self.0.dim() {
-            panic!("Provided operator dimension does not match state dimension");
-        }
-        self.0 = (op.get() * &self.0).unwrap();
+    pub fn apply(&mut self, op: &Gate) -> Result<(), Error> {
+        self.0 = (op.get() * &self.0)?;
+        Ok(())
     }
 
 
-    pub fn apply_partial(&mut self, interval: Range<usize> ,op: &Gate) {
-        assert_eq!(2usize.pow(interval.len() as u32), op.dim());
+    pub fn apply_partial(&mut self, interval: Range<usize> ,op: &Gate) -> Result<(), Error> {
+        let size = 2usize.pow(interval.len() as u32);
+        if size != op.dim() {
+            return Err(Error::DimensionMismatch { left: op.get().dim(), right: (size, 1) });
+        }
         
         let left_size = 2_usize.pow(interval.start as u32);
         let right_size = self.0.dim() / 2_usize.pow(interval.end as u32);
 
-        let left_id = Gate::identity(left_size);
-        let right_id = Gate::identity(right_size);
-
-        let full_op = left_id.tensor_product(op).tensor_product(&right_id);
+        // Lazily, so the identities on either side never get built
+        let full_op = Kronecker::identity(left_size).tensor_product(&Kronecker::from(op)).tensor_product(&Kronecker::identity(right_size));
+        self.0 = (&full_op * &self.0)?;
+        Ok(())
+    }
 
-        self.apply(&full_op)
+    // Every factor has to be unitary, identities aren't checked
+    pub fn apply_kronecker(&mut self, op: &Kronecker<C64>) -> Result<(), Error> {
+        for factor in op.factors() {
+            Gate::try_from(factor.clone())?;
+        }
+        self.0 = (op * &self.0)?;
+        Ok(())
     }
 
     pub fn num_qubits(&self) -> usize {
@@ -63,126 +81,254 @@ impl State {
         state
     }
 
-    pub fn measure(&mut self) -> usize {
-        let mut prob_prefix_sum = Vec::with_capacity(self.0.dim());
-        let mut prob = 0.0;
-        for i in 0..(1 << self.num_qubits()) {
-            prob += self.0.get(i).modulus_squared();
-            prob_prefix_sum.push(prob);
-        }
+    pub fn probabilities(&self) -> Vec<f64> {
+        let mut probabilities = vec![0.0; self.0.dim()];
+        for_each_chunk_mut(&mut probabilities, |offset, chunk| {
+            for (prob, entry) in chunk.iter_mut().zip(&self.0.data[offset..]) {
+                *prob = entry.modulus_squared();
+            }
+        });
+        probabilities
+    }
 
-        let mut measured = (1 << self.num_qubits());
-        while measured == 1 << self.num_qubits() {
-            let rand: u64 = random::<u64>().min(u64::MAX - 1);
-            let sample = (rand as f64) / (u64::MAX as f64);
-    
-            measured = prob_prefix_sum.binary_search_by(|probe| {
-                probe.partial_cmp(&sample).unwrap().then(std::cmp::Ordering::Greater)
-            }).unwrap_err();
-        }
+    // Marginal distribution of the qubits in interval
+    pub fn probabilities_partial(&self, interval: Range<usize>) -> Vec<f64> {
+        self.probabilities_qubits(&interval.collect::<Vec<_>>())
+    }
 
-        
-        self.0.data.iter_mut().for_each(|entry| *entry = C64::ZERO);
+    pub fn measure(&mut self) -> usize {
+        let measured = sample(&self.probabilities());
+
+        for_each_chunk_mut(&mut self.0.data, |_, chunk| chunk.fill(C64::ZERO));
         self.0.data[measured] = C64::ONE;
         measured
     }
 
     pub fn measure_partial(self, interval: Range<usize>) -> (usize, Self){
-        let q = self.num_qubits();
-        
-        let mut prob_prefix_sum = Vec::new();
-
-        let mut prob = 0.0;
-        for m in 0..(1 << interval.len()) {
-            for l in 0..(1 << interval.start) {
-                for r in 0..(1 << (q - interval.end)) {
-                    let k = (l << (q - interval.start)) + (m << (q - interval.end)) + r;
-                    prob += self.0.get(k).modulus_squared();
-                }
+        self.measure_qubits(&interval.collect::<Vec<_>>())
+    }
+
+    pub fn measure_partial_leave_state(&mut self, interval: Range<usize>) -> usize {
+        self.measure_qubits_leave_state(&interval.collect::<Vec<_>>())
+    }
+
+    // Every qubit must exist and appear once, the kernels below rely on it for their indexing
+    fn check_qubits(&self, qubits: &[usize]) -> Result<(), Error> {
+        let num_qubits = self.num_qubits();
+        for (i, &qubit) in qubits.iter().enumerate() {
+            if qubit >= num_qubits {
+                return Err(Error::QubitOutOfRange { qubit, num_qubits });
+            }
+            if qubits[..i].contains(&qubit) {
+                return Err(Error::RepeatedQubit { qubit });
             }
-            prob_prefix_sum.push(prob);
         }
+        Ok(())
+    }
 
-        let mut measured = (1 << interval.len());
-        while measured == (1 << interval.len()){
-            let random_u64 = random::<u64>().min(u64::MAX - 1);
-            let random_sample = (random_u64 as f64) / (u64::MAX as f64);
-    
-            measured = prob_prefix_sum.binary_search_by(|probe| {
-                probe.partial_cmp(&random_sample).unwrap().then(std::cmp::Ordering::Greater)
-            }).unwrap_err();
+    /// Applies op to the given qubits (in any order, the first one acting as the most significant), without building the full operator.
+    pub fn apply_qubits(&mut self, qubits: &[usize], op: &Gate) -> Result<(), Error> {
+        self.check_qubits(qubits)?;
+        if 1 << qubits.len() != op.dim() {
+            return Err(Error::DimensionMismatch { left: op.get().dim(), right: (1 << qubits.len(), 1) });
         }
 
-        let mut new_state_vector = Vector::<C64>::zero(1 << (q - interval.len()));
-        for l in 0..(1 << interval.start) {
-            for r in 0..(1 << (q - interval.end)) {
-                let k = (l << (q - interval.start)) + (measured << (q - interval.end)) + r;
-                new_state_vector.data[l * (1 << (q - interval.end)) + r] += self.0.get(k);
-            }
-        }
-        new_state_vector.normalize();
-        
-        let new_state = State::try_from(new_state_vector).expect("Could not create remaning state");
+        let q = self.num_qubits();
+        let rest: Vec<usize> = (0..q).filter(|qubit| !qubits.contains(qubit)).collect();
+        let offsets: Vec<usize> = (0..op.dim()).map(|m| spread(q, m, qubits)).collect();
 
-        (measured, new_state)
+        // Amplitudes sharing their non target bits form a group op mixes among itself, every group is gathered, multiplied and scattered back in place by one thread
+        let amplitudes = Amplitudes(self.0.data.as_mut_ptr());
+        for_each_range(self.0.dim() >> qubits.len(), |groups| {
+            let mut gathered = vec![C64::ZERO; op.dim()];
+            for group in groups {
+                let base = spread(q, group, &rest);
+                // SAFETY: the qubits are distinct and in range, so base | offset stays in bounds and the groups handed to different threads never share an index
+                unsafe {
+                    for (entry, offset) in gathered.iter_mut().zip(&offsets) {
+                        *entry = *amplitudes.get().add(base | offset);
+                    }
+                    for (row, offset) in op.get().data.chunks(op.dim()).zip(&offsets) {
+                        *amplitudes.get().add(base | offset) = row.iter().zip(&gathered).fold(C64::ZERO, |acc, (&entry, &amplitude)| acc + entry * amplitude);
+                    }
+                }
+            }
+        });
+        Ok(())
     }
-    
 
-    pub fn measure_partial_leave_state(&mut self, interval: Range<usize>) -> usize {
+    // Marginal distribution of the given qubits (the first one being the most significant), which must be distinct and in range
+    pub fn probabilities_qubits(&self, qubits: &[usize]) -> Vec<f64> {
+        if let Err(error) = self.check_qubits(qubits) { panic!("{error}") }
         let q = self.num_qubits();
-        
-        let mut prob_prefix_sum = Vec::new();
-
-        let mut prob = 0.0;
-        for m in 0..(1 << interval.len()) {
-            for l in 0..(1 << interval.start) {
-                for r in 0..(1 << (q - interval.end)) {
-                    let k = (l << (q - interval.start)) + (m << (q - interval.end)) + r;
-                    prob += self.0.get(k).modulus_squared();
+        let outcomes = 1 << qubits.len();
+
+        if outcomes < CHUNK_SIZE {
+            // Few outcomes, every chunk of the state gets its own histogram
+            let histograms = map_chunks(&self.0.data, |offset, chunk| {
+                let mut probabilities = vec![0.0; outcomes];
+                for (i, entry) in chunk.iter().enumerate() {
+                    probabilities[extract(q, offset + i, qubits)] += entry.modulus_squared();
                 }
-            }
-            prob_prefix_sum.push(prob);
+                probabilities
+            });
+            histograms.into_iter().fold(vec![0.0; outcomes], |mut acc, histogram| {
+                acc.iter_mut().zip(histogram).for_each(|(acc, prob)| *acc += prob);
+                acc
+            })
+        } else {
+            // Many outcomes, each one sums the basis states it covers
+            let rest: Vec<usize> = (0..q).filter(|qubit| !qubits.contains(qubit)).collect();
+            let mut probabilities = vec![0.0; outcomes];
+            for_each_chunk_mut(&mut probabilities, |offset, chunk| {
+                for (i, prob) in chunk.iter_mut().enumerate() {
+                    let base = spread(q, offset + i, qubits);
+                    *prob = (0..1 << rest.len()).map(|r| self.0.data[base | spread(q, r, &rest)].modulus_squared()).sum();
+                }
+            });
+            probabilities
         }
+    }
 
-        let mut measured = (1 << interval.len());
-        while measured == (1 << interval.len()){
-            let random_u64 = random::<u64>().min(u64::MAX - 1);
-            let random_sample = (random_u64 as f64) / (u64::MAX as f64);
-    
-            measured = prob_prefix_sum.binary_search_by(|probe| {
-                probe.partial_cmp(&random_sample).unwrap().then(std::cmp::Ordering::Greater)
-            }).unwrap_err();
-        }
-        
-        //Zero out states that don't match measurement
-        for m in 0..(1 << interval.len()) {
-            if m == measured { continue; }
-            for l in 0..(1 << interval.start) {
-                for r in 0..(1 << (q - interval.end)) {
-                    let k = (l << (q - interval.start)) + (m << (q - interval.end)) + r;
-                    self.0.data[k] = C64::ZERO;
+    pub fn measure_qubits_leave_state(&mut self, qubits: &[usize]) -> usize {
+        if let Err(error) = self.check_qubits(qubits) { panic!("{error}") }
+        let q = self.num_qubits();
+        let measured = sample(&self.probabilities_qubits(qubits));
+
+        for_each_chunk_mut(&mut self.0.data, |offset, chunk| {
+            for (i, entry) in chunk.iter_mut().enumerate() {
+                if extract(q, offset + i, qubits) != measured {
+                    *entry = C64::ZERO;
                 }
             }
-            prob_prefix_sum.push(prob);
-        }
-        self.0.normalize();
+        });
+        self.normalize();
 
         measured
     }
+
+    // Measures the given qubits and removes them, the remaining ones keep their order
+    pub fn measure_qubits(mut self, qubits: &[usize]) -> (usize, Self) {
+        if let Err(error) = self.check_qubits(qubits) { panic!("{error}") }
+        let q = self.num_qubits();
+        let measured = self.measure_qubits_leave_state(qubits);
+        let remaining: Vec<usize> = (0..q).filter(|qubit| !qubits.contains(qubit)).collect();
+
+        let base = spread(q, measured, qubits);
+        let mut new_state_vector = Vector::<C64>::zero(1 << remaining.len());
+        for_each_chunk_mut(&mut new_state_vector.data, |offset, chunk| {
+            for (i, entry) in chunk.iter_mut().enumerate() {
+                *entry = self.0.data[base | spread(q, offset + i, &remaining)];
+            }
+        });
+
+        (measured, Self(new_state_vector))
+    }
+
+    fn normalize(&mut self) {
+        let norm = sum(&self.probabilities()).sqrt();
+        for_each_chunk_mut(&mut self.0.data, |_, chunk| {
+            for entry in chunk.iter_mut() {
+                *entry /= C64::new(norm, 0.0);
+            }
+        });
+    }
+}
+
+/// Schmidt decomposition |ψ⟩ = Σ λ_i |a_i⟩|b_i⟩ of a state split in two, only the non zero coefficients are kept (in descending order).
+pub struct Schmidt {
+    pub coefficients: Vec<f64>,
+    // States |a_i⟩ of the qubits before the cut
+    pub left: Vec<Vector<C64>>,
+    // States |b_i⟩ of the qubits after the cut
+    pub right: Vec<Vector<C64>>
+}
+
+impl Schmidt {
+    pub fn rank(&self) -> usize {
+        self.coefficients.len()
+    }
+
+    // Von Neumann entropy of either half, in bits
+    pub fn entanglement_entropy(&self) -> f64 {
+        self.coefficients.iter().map(|coefficient| coefficient * coefficient).map(|p| -p * p.log2()).sum()
+    }
+}
+
+impl State {
+    // The qubits 0..cut form one half, the rest the other
+    pub fn schmidt_decomposition(&self, cut: usize) -> Result<Schmidt, Error> {
+        let q = self.num_qubits();
+        if cut > q {
+            return Err(Error::TooManyQubits { qubits: cut, num_qubits: q });
+        }
+
+        // ψ as a (2^cut x 2^(q - cut)) matrix, so ψ = Σ σ_i u_i ⊗ conj(v_i)
+        let (rows, cols) = (1 << cut, 1 << (q - cut));
+        let psi = Matrix::from_rows(self.0.data.chunks(cols).map(Vector::from), Some(rows))?;
+        let Svd { u, singular_values, v } = psi.svd();
+
+        let tolerance = f64::EPSILON * rows.max(cols) as f64;
+        let rank = singular_values.iter().take_while(|&&sigma| sigma > tolerance).count();
+        Ok(Schmidt {
+            coefficients: singular_values[..rank].to_vec(),
+            left: (0..rank).map(|i| u.col(i).unwrap().to_vector()).collect(),
+            right: (0..rank).map(|i| Vector::from_iter(v.col(i).unwrap().iter().map(|entry| entry.conjugate()), Some(cols))).collect()
+        })
+    }
+}
+
+// Value of the given qubits in basis state k of a q qubit state, the first qubit being the most significant bit
+fn extract(q: usize, k: usize, qubits: &[usize]) -> usize {
+    qubits.iter().fold(0, |acc, &qubit| (acc << 1) | ((k >> (q - 1 - qubit)) & 1))
+}
+
+// Inverse of extract, the basis state with value m on qubits and 0 everywhere else
+fn spread(q: usize, m: usize, qubits: &[usize]) -> usize {
+    let len = qubits.len();
+    qubits.iter().enumerate().fold(0, |acc, (i, &qubit)| acc | (((m >> (len - 1 - i)) & 1) << (q - 1 - qubit)))
+}
+
+// Amplitudes threads write to through disjoint indices
+struct Amplitudes(*mut C64);
+
+unsafe impl Sync for Amplitudes {}
+
+impl Amplitudes {
+    // Through a method so closures capture the wrapper rather than the bare pointer
+    fn get(&self) -> *mut C64 {
+        self.0
+    }
+}
+
+// Draws an outcome from a distribution summing to (about) 1
+pub(super) fn sample(probabilities: &[f64]) -> usize {
+    let prob_prefix_sum = prefix_sums(probabilities);
+
+    let mut measured = probabilities.len();
+    while measured == probabilities.len() {
+        let random_u64 = random::<u64>().min(u64::MAX - 1);
+        let random_sample = (random_u64 as f64) / (u64::MAX as f64);
+
+        measured = prob_prefix_sum.binary_search_by(|probe| {
+            probe.partial_cmp(&random_sample).unwrap().then(std::cmp::Ordering::Greater)
+        }).unwrap_err();
+    }
+    measured
 }
 
 impl TryFrom<Vector<C64>> for State {
-    type Error = ();
+    type Error = Error;
     fn try_from(value: Vector<C64>) -> Result<Self, Self::Error> {
         if !value.dim().is_power_of_two() {
-            return Err(())
+            return Err(Error::NotPowerOfTwo { dim: value.dim() })
         }
 
         let mut sum = value.iter().fold(0.0, |acc, cur| acc + cur.modulus_squared());
         if (sum - 1.0).abs() < f64::EPSILON * 10.0 {
             Ok(Self(value))
         } else {
-            Err(())
+            Err(Error::NotNormalized { norm: sum.sqrt() })
         }
     }
 }
@@ -190,7 +336,9 @@ impl TryFrom<Vector<C64>> for State {
 #[cfg(test)]
 mod tests {
     use crate::dynamic::vector::Vector;
+    use crate::dynamic::gate::Gate;
     use crate::complex::*;
+    use crate::error::Error;
     use super::State;
 
     #[test]
@@ -202,7 +350,131 @@ mod tests {
 
         assert!(TryInto::<State>::try_into(a).is_ok());
         assert!(TryInto::<State>::try_into(b).is_ok());
-        assert!(TryInto::<State>::try_into(c).is_err());
-        assert!(TryInto::<State>::try_into(d).is_err());
+        assert_eq!(TryInto::<State>::try_into(c).unwrap_err(), Error::NotNormalized { norm: 0.0 });
+        assert!(matches!(TryInto::<State>::try_into(d), Err(Error::NotNormalized { .. })));
+        assert_eq!(State::try_from(dvec64![1;0;0]).unwrap_err(), Error::NotPowerOfTwo { dim: 3 });
+
+        let mut state = State::from_qubit(false);
+        assert_eq!(state.apply(&Gate::cnot()), Err(Error::DimensionMismatch { left: (4, 4), right: (2, 1) }));
+        assert!(state.apply_qubits(&[0], &Gate::cnot()).is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_qubit_kernels() {
+        // |010>, flipping qubits 2 and 0 (in that order) with a CNOT controlled by qubit 2 does nothing, controlled by qubit 1 flips qubit 0
+        let mut state = State::from_qubits([false, true, false].into_iter());
+        state.apply_qubits(&[2, 0], &Gate::cnot()).unwrap();
+        assert_eq!(state.probabilities()[0b010], 1.0);
+        state.apply_qubits(&[1, 0], &Gate::cnot()).unwrap();
+        assert_eq!(state.probabilities()[0b110], 1.0);
+
+        // Same as applying H TENSOR H to qubits 0..2
+        let mut partial = state.clone();
+        partial.apply_partial(0..2, &Gate::hadamard().tensor_product(&Gate::hadamard())).unwrap();
+        state.apply_qubits(&[0], &Gate::hadamard()).unwrap();
+        state.apply_qubits(&[1], &Gate::hadamard()).unwrap();
+        assert!(state.get().iter().zip(partial.get().iter()).all(|(a, b)| (*a - *b).modulus() < 1e-9));
+        let close = |a: &[f64], b: &[f64]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-9);
+        assert!(close(&state.probabilities_qubits(&[2, 0]), &[0.5, 0.5, 0.0, 0.0]));
+
+        let (measured, remaining) = state.measure_qubits(&[1]);
+        assert!(measured < 2);
+        assert_eq!(remaining.num_qubits(), 2);
+        assert!(close(&remaining.probabilities_qubits(&[0]), &[0.5, 0.5]));
+        assert!(close(&remaining.probabilities_qubits(&[1]), &[1.0, 0.0]));
+
+        // Qubits must exist and be distinct
+        let mut state = State::from_qubits([false; 2].into_iter());
+        assert_eq!(state.apply_qubits(&[2], &Gate::hadamard()), Err(Error::QubitOutOfRange { qubit: 2, num_qubits: 2 }));
+        assert_eq!(state.apply_qubits(&[1, 1], &Gate::cnot()), Err(Error::RepeatedQubit { qubit: 1 }));
+        assert_eq!(state.probabilities()[0], 1.0);
+        for qubits in [[0, 5], [1, 1]] {
+            assert!(std::panic::catch_unwind(|| state.probabilities_qubits(&qubits)).is_err());
+            assert!(std::panic::catch_unwind(|| state.clone().measure_qubits(&qubits)).is_err());
+            assert!(std::panic::catch_unwind(|| state.clone().measure_qubits_leave_state(&qubits)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_thread_count() {
+        use crate::dynamic::set_num_threads;
+
+        // Puts the thread count back even when an assert fails, so it doesn't leak into other tests
+        struct ResetThreads;
+        impl Drop for ResetThreads {
+            fn drop(&mut self) {
+                set_num_threads(0);
+            }
+        }
+        let _reset = ResetThreads;
+
+        // 14 qubits spans several chunks, the results should be bit for bit the same with any number of threads
+        let run = |threads: usize| {
+            set_num_threads(threads);
+            let mut state = State::from_qubits((0..14).map(|i| i % 3 == 0));
+            for qubit in 0..14 {
+                state.apply_qubits(&[qubit], &Gate::hadamard()).unwrap();
+            }
+            state.apply_qubits(&[13, 2], &Gate::cnot()).unwrap();
+            state.apply_qubits(&[5], &Gate::phase_shift(0.3)).unwrap();
+            state.apply_qubits(&[0, 7, 12], &Gate::fredkin()).unwrap();
+            let marginals = (state.probabilities_qubits(&[3, 1]), state.probabilities_qubits(&(0..13).collect::<Vec<_>>()));
+            state.measure_qubits_leave_state(&[4]);
+            (state.get().clone(), marginals)
+        };
+
+        let (single, single_marginals) = run(1);
+        for threads in [2, 3, 8] {
+            let (state, marginals) = run(threads);
+            assert_eq!(marginals, single_marginals);
+            // The measurement itself is random, so only compare amplitudes when it went the same way
+            let same_outcome = state.iter().zip(single.iter()).all(|(a, b)| (*a == C64::ZERO) == (*b == C64::ZERO));
+            if same_outcome {
+                assert_eq!(state, single);
+            }
+        }
+        drop(_reset);
+
+        let close = |a: &[f64], b: &[f64]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-9);
+        assert!(close(&single_marginals.0, &[0.25; 4]));
+        assert!((single_marginals.1.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_schmidt_decomposition() {
+        let product = State::from_qubits([true, false, true].into_iter());
+        let schmidt = product.schmidt_decomposition(1).unwrap();
+        assert_eq!(schmidt.rank(), 1);
+        assert!(schmidt.entanglement_entropy().abs() < 1e-9);
+
+        // GHZ state, one bit of entanglement across any cut
+        let mut ghz = State::from_qubits([false; 3].into_iter());
+        ghz.apply_qubits(&[0], &Gate::hadamard()).unwrap();
+        ghz.apply_qubits(&[0, 1], &Gate::cnot()).unwrap();
+        ghz.apply_qubits(&[1, 2], &Gate::cnot()).unwrap();
+        for cut in 1..3 {
+            let schmidt = ghz.schmidt_decomposition(cut).unwrap();
+            assert_eq!(schmidt.rank(), 2);
+            assert!(schmidt.coefficients.iter().all(|coefficient| (coefficient - 0.5_f64.sqrt()).abs() < 1e-9));
+            assert!((schmidt.entanglement_entropy() - 1.0).abs() < 1e-9);
+        }
+        assert_eq!(ghz.schmidt_decomposition(0).unwrap().rank(), 1);
+        assert_eq!(ghz.schmidt_decomposition(4).err(), Some(Error::TooManyQubits { qubits: 4, num_qubits: 3 }));
+        assert_eq!(ghz.schmidt_decomposition(64).err(), Some(Error::TooManyQubits { qubits: 64, num_qubits: 3 }));
+
+        // Σ λ_i |a_i⟩|b_i⟩ gives the state back
+        let mut state = State::from_qubits([false, true, false, false].into_iter());
+        for qubit in 0..4 {
+            state.apply_qubits(&[qubit], &Gate::hadamard()).unwrap();
+        }
+        state.apply_qubits(&[3, 0], &Gate::cnot()).unwrap();
+        state.apply_qubits(&[2], &Gate::phase_shift(0.7)).unwrap();
+        state.apply_qubits(&[1, 2], &Gate::cnot()).unwrap();
+        let schmidt = state.schmidt_decomposition(2).unwrap();
+        let rebuilt = schmidt.coefficients.iter().zip(schmidt.left.iter().zip(&schmidt.right))
+            .map(|(&coefficient, (a, b))| a.tensor_product(b) * C64::new(coefficient, 0.0))
+            .reduce(|acc, term| (acc + &term).unwrap()).unwrap();
+        assert!(rebuilt.iter().zip(state.get().iter()).all(|(a, b)| (*a - *b).modulus() < 1e-9));
+        assert!((schmidt.coefficients.iter().map(|coefficient| coefficient * coefficient).sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+}
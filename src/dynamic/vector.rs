@@ -1,4 +1,5 @@
 use crate::complex::*;
+use crate::error::Error;
 use std::slice::Iter;
 use std::fmt::{Display, Write};
 
@@ -8,7 +9,7 @@ pub struct Vector<F: Complex> {
     pub data: Vec<F>,
 }
 
-use std::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+use std::ops::{Add, AddAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign};
 
 // MARK: Vector
 impl<F: Complex> Vector<F> {
@@ -31,7 +32,7 @@ impl<F: Complex> Vector<F> {
         }
     }
 
-    pub fn dot(&self, rhs: &Self) -> Result<F, ()>{
+    pub fn dot(&self, rhs: &Self) -> Result<F, Error>{
         if rhs.dim() == self.dim() {
             let mut result = F::ZERO;
             for i in 0..self.dim() {
@@ -39,7 +40,7 @@ impl<F: Complex> Vector<F> {
             }
             Ok(result)
         } else {
-            Err(())
+            Err(self.mismatch(rhs))
         }
     }
 
@@ -47,8 +48,20 @@ impl<F: Complex> Vector<F> {
         self.data.len()
     }
 
-    pub fn get(&self, index: usize) -> F {
-        self.data[index]
+    fn mismatch(&self, rhs: &Self) -> Error {
+        Error::DimensionMismatch { left: (self.dim(), 1), right: (rhs.dim(), 1) }
+    }
+
+    pub fn get(&self, index: usize) -> Option<F> {
+        self.data.get(index).copied()
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut F> {
+        self.data.get_mut(index)
+    }
+
+    pub fn view(&self) -> VectorView<'_, F> {
+        VectorView::new(&self.data, 1, self.dim())
     }
 
     pub fn normalize(&mut self) {
@@ -102,6 +115,80 @@ impl<F: Complex> Display for Vector<F> {
     }
 }
 
+impl<F: Complex> Index<usize> for Vector<F> {
+    type Output = F;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.data[index]
+    }
+}
+
+impl<F: Complex> IndexMut<usize> for Vector<F> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.data[index]
+    }
+}
+
+// MARK: VectorView
+// Borrowed, possibly strided, run of entries, e.g. a row or column of a matrix
+#[derive(Clone, Copy, Debug)]
+pub struct VectorView<'a, F: Complex> {
+    data: &'a [F],
+    stride: usize,
+    len: usize
+}
+
+impl<'a, F: Complex> VectorView<'a, F> {
+    // Entry i is data[i * stride]
+    pub(super) fn new(data: &'a [F], stride: usize, len: usize) -> Self {
+        let data = if len == 0 { &[] } else { &data[..(len - 1) * stride + 1] };
+        Self { data, stride, len }
+    }
+
+    pub fn dim(&self) -> usize {
+        self.len
+    }
+
+    pub fn get(&self, index: usize) -> Option<F> {
+        (index < self.len).then(|| self.data[index * self.stride])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &'a F> {
+        self.data.iter().step_by(self.stride)
+    }
+
+    pub fn dot(&self, rhs: &Self) -> Result<F, Error> {
+        if self.dim() != rhs.dim() {
+            return Err(Error::DimensionMismatch { left: (self.dim(), 1), right: (rhs.dim(), 1) });
+        }
+        Ok(self.iter().zip(rhs.iter()).fold(F::ZERO, |acc, (&a, &b)| acc + a.conjugate() * b))
+    }
+
+    pub fn to_vector(&self) -> Vector<F> {
+        Vector::from_iter(self.iter().copied(), Some(self.len))
+    }
+}
+
+impl<F: Complex> Index<usize> for VectorView<'_, F> {
+    type Output = F;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        assert!(index < self.len, "index {index} out of bounds for a view of length {}", self.len);
+        &self.data[index * self.stride]
+    }
+}
+
+impl<F: Complex> Display for VectorView<'_, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_char('[')?;
+        for entry in self.iter() {
+            f.write_fmt(format_args!("{entry},"))?;
+        }
+        f.write_char(']')?;
+        Ok(())
+    }
+}
+
 impl<F: Complex> From<&[F]> for Vector<F> {
     fn from(value: &[F]) -> Self {
         Self {
@@ -121,13 +208,13 @@ impl<F: Complex> AddAssign<&Self> for Vector<F> {
 
 
 impl<F: Complex> Add<&Self> for Vector<F> {
-    type Output = Result<Self, ()>;
+    type Output = Result<Self, Error>;
 
     fn add(mut self, rhs: &Self) -> Self::Output {
-        if self.dim() != rhs.dim() {return Err(()); }
+        if self.dim() != rhs.dim() {return Err(self.mismatch(rhs)); }
 
         for (i, elem) in self.data.iter_mut().enumerate() {
-            elem.add_assign(rhs.get(i));
+            elem.add_assign(rhs[i]);
         }
         
         Ok(self)
@@ -144,10 +231,10 @@ impl<F: Complex> SubAssign<&Self> for Vector<F> {
 }
 
 impl<F: Complex> Sub<&Self> for Vector<F> {
-    type Output = Result<Self, ()>;
+    type Output = Result<Self, Error>;
 
     fn sub(mut self, rhs: &Self) -> Self::Output {
-        if self.dim() != rhs.dim() {return Err(()); }
+        if self.dim() != rhs.dim() {return Err(self.mismatch(rhs)); }
         for (i, comp) in self.data.iter_mut().enumerate() {
             comp.sub_assign(rhs.data[i]);
         }
@@ -233,6 +320,7 @@ mod tests {
     fn test_inner_product() {
         let a = dvec64![1,-1 ; 3];
         assert!(a.dot(&a).unwrap().r > 0.0);
+        assert_eq!(a.dot(&dvec64![1]), Err(Error::DimensionMismatch { left: (2, 1), right: (1, 1) }));
 
         let b = dvec64![0.0; 0.0];
         assert_eq!(b.dot(&b).unwrap(), C64::ZERO);
@@ -0,0 +1,699 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::rc::Rc;
+
+use super::emulator::{find_block_end, split_lines};
+use super::lexer::{PrimitiveGate, Token, TokenType};
+use super::registers::{DiscardError, Register, RegisterMap, RegisterTable};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// Runs, but probably not the way it was meant to
+    Warning,
+    /// Fails at runtime
+    Error
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error"
+        })
+    }
+}
+
+/// A problem found by `check`, located at the token it is about.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    token: Token
+}
+
+impl Diagnostic {
+    pub fn token(&self) -> &Token {
+        &self.token
+    }
+
+    pub fn line(&self) -> usize {
+        self.token.line()
+    }
+
+    pub fn col(&self) -> usize {
+        self.token.col()
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}, at {}:{}", self.severity, self.message, self.line(), self.col())?;
+        if let Some(file) = self.token.file() {
+            write!(f, " in {}", file.display())?;
+        }
+        Ok(())
+    }
+}
+
+// What is known about a register besides its qubits, which are laid out as the emulator would
+#[derive(Clone)]
+struct RegisterInfo {
+    definition: usize,
+    // Definitions of the registers this one was SELECTed from
+    ancestors: Vec<usize>
+}
+
+#[derive(Clone)]
+struct OperatorInfo {
+    // None when an earlier error made it unknowable
+    dim: Option<usize>,
+    // None for primitive gates
+    definition: Option<usize>
+}
+
+struct SubroutineInfo {
+    params: Vec<String>,
+    body: Vec<Vec<Token>>,
+    definition: usize
+}
+
+// Mirrors the emulator's scoping rules, see emulator::Scope
+#[derive(Clone, Default)]
+struct Scope {
+    registers: RegisterMap<RegisterInfo>,
+    operators: HashMap<String, OperatorInfo>,
+    subroutines: HashMap<String, Rc<SubroutineInfo>>
+}
+
+struct Definition {
+    token: Token,
+    name: String,
+    kind: &'static str,
+    used: bool
+}
+
+#[derive(Default)]
+struct Checker {
+    diagnostics: Vec<Diagnostic>,
+    definitions: Vec<Definition>,
+    registers: RegisterTable,
+    // By qubit, the ones which were measured and not acted on since
+    measured: Vec<bool>,
+    // Subroutines whose bodies are being checked
+    call_stack: Vec<String>
+}
+
+fn same_position(a: &Token, b: &Token) -> bool {
+    a.line() == b.line() && a.col() == b.col() && a.file() == b.file()
+}
+
+/// Walks a program without running it and reports undefined names, operator / register size mismatches, overlapping SELECTs,
+/// operators applied to measured qubits and unused definitions. Diagnostics are ordered by position.
+pub fn check(tokens: &[Token]) -> Vec<Diagnostic> {
+    let mut checker = Checker::default();
+    checker.check_lines(&split_lines(tokens), &mut Scope::default());
+
+    let unused: Vec<Diagnostic> = checker.definitions.iter()
+        .filter(|definition| !definition.used)
+        .map(|definition| Diagnostic {
+            severity: Severity::Warning,
+            message: format!("{} {} is never used", definition.kind, definition.name),
+            token: definition.token.clone()
+        })
+        .collect();
+
+    let mut diagnostics = checker.diagnostics;
+    diagnostics.extend(unused);
+    diagnostics.sort_by(|a, b| (a.token.file(), a.line(), a.col()).cmp(&(b.token.file(), b.line(), b.col())));
+    diagnostics
+}
+
+impl Checker {
+    // Bodies are checked once per call / up to twice per REPEAT, so the same problem can come up more than once
+    fn report(&mut self, severity: Severity, token: &Token, message: String) {
+        let duplicate = self.diagnostics.iter().any(|diagnostic| diagnostic.message == message && same_position(&diagnostic.token, token));
+        if !duplicate {
+            self.diagnostics.push(Diagnostic { severity, message, token: token.clone() });
+        }
+    }
+
+    fn define(&mut self, token: &Token, name: &str, kind: &'static str) -> usize {
+        match self.definitions.iter().position(|definition| same_position(&definition.token, token)) {
+            Some(index) => index,
+            None => {
+                self.definitions.push(Definition { token: token.clone(), name: name.to_owned(), kind, used: false });
+                self.definitions.len() - 1
+            }
+        }
+    }
+
+    fn identifier<'t>(&mut self, token: Option<&'t Token>, first_token: &Token, label: &str) -> Option<&'t str> {
+        match token {
+            Some(Token { ty: TokenType::Identifier(name), ..}) => Some(name),
+            Some(token) => {
+                self.report(Severity::Error, token, format!("Expected {label} (IDENTIFIER), found {}", token.ty));
+                None
+            },
+            None => {
+                self.report(Severity::Error, first_token, format!("Missing {label} (IDENTIFIER) for {}", first_token.ty));
+                None
+            }
+        }
+    }
+
+    fn number(&mut self, token: Option<&Token>, first_token: &Token, label: &str) -> Option<usize> {
+        match token {
+            Some(Token { ty: TokenType::Number(n), ..}) => Some(*n),
+            Some(token) => {
+                self.report(Severity::Error, token, format!("Expected {label} (NUMBER), found {}", token.ty));
+                None
+            },
+            None => {
+                self.report(Severity::Error, first_token, format!("Missing {label} (NUMBER) for {}", first_token.ty));
+                None
+            }
+        }
+    }
+
+    fn register(&mut self, token: Option<&Token>, first_token: &Token, scope: &Scope) -> Option<Register<RegisterInfo>> {
+        let name = self.identifier(token, first_token, "register name")?;
+        match scope.registers.get(name) {
+            Some(register) => {
+                self.definitions[register.info.definition].used = true;
+                Some(register.clone())
+            },
+            None => {
+                self.report(Severity::Error, token.unwrap(), format!("Register {name} does not exist at this point"));
+                None
+            }
+        }
+    }
+
+    fn operator(&mut self, token: Option<&Token>, first_token: &Token, scope: &Scope) -> Option<OperatorInfo> {
+        match token {
+            Some(Token { ty: TokenType::Gate(primitive), ..}) => {
+                let dim = match primitive {
+                    PrimitiveGate::H | PrimitiveGate::R(_) => 2,
+                    PrimitiveGate::CNOT => 4,
+                    PrimitiveGate::I(n) => *n
+                };
+                Some(OperatorInfo { dim: Some(dim), definition: None })
+            },
+            Some(token @ Token { ty: TokenType::Identifier(name), ..}) => match scope.operators.get(name) {
+                Some(operator) => {
+                    if let Some(definition) = operator.definition {
+                        self.definitions[definition].used = true;
+                    }
+                    Some(operator.clone())
+                },
+                None => {
+                    self.report(Severity::Error, token, format!("Operator {name} does not exist at this point"));
+                    None
+                }
+            },
+            Some(token) => {
+                self.report(Severity::Error, token, format!("Expected operator (IDENTIFIER or primitive gate), found {}", token.ty));
+                None
+            },
+            None => {
+                self.report(Severity::Error, first_token, format!("Missing operator (IDENTIFIER or primitive gate) for {}", first_token.ty));
+                None
+            }
+        }
+    }
+
+    fn is_measured(&self, register: &Register<RegisterInfo>) -> bool {
+        register.qubits.iter().any(|&qubit| self.measured[qubit])
+    }
+
+    fn set_measured(&mut self, register: &Register<RegisterInfo>, measured: bool) {
+        for &qubit in &register.qubits {
+            self.measured[qubit] = measured;
+        }
+    }
+
+    fn check_lines(&mut self, lines: &[&[Token]], scope: &mut Scope) {
+        let mut line_index = 0;
+        while line_index < lines.len() {
+            let line = lines[line_index];
+            let mut next_line = line_index + 1;
+
+            let Some(first_token) = line.first() else {
+                line_index = next_line;
+                continue;
+            };
+            let args: Vec<&Token> = line[1..].iter().take_while(|token| !matches!(token.ty, TokenType::NewLine)).collect();
+            let arg = |i: usize| args.get(i).copied();
+
+            // Number of arguments the instruction takes, anything after them is an error
+            let arity = match &first_token.ty {
+                TokenType::Initialize => {
+                    let name = self.identifier(arg(0), first_token, "register name");
+
+                    // INITIALIZE R N, INITIALIZE R N [BITS] or INITIALIZE R [BITS]
+                    let (num_qubits, arity) = match arg(1).map(|token| &token.ty) {
+                        Some(TokenType::Number(n)) => match arg(2).map(|token| &token.ty) {
+                            Some(TokenType::ByteArray(bits)) if bits.len() != *n => {
+                                self.report(Severity::Error, args[2], format!("Expected {n} bits for the initial register state, found {}", bits.len()));
+                                (None, 3)
+                            },
+                            Some(TokenType::ByteArray(_)) => (Some(*n), 3),
+                            _ => (Some(*n), 2)
+                        },
+                        Some(TokenType::ByteArray(bits)) => (Some(bits.len()), 2),
+                        Some(ty) => {
+                            self.report(Severity::Error, args[1], format!("Expected number of qubits (NUMBER) or initial state (BYTEARRAY), found {ty}"));
+                            (None, 2)
+                        },
+                        None => {
+                            self.report(Severity::Error, first_token, "Missing number of qubits (NUMBER) for INITIALIZE".to_owned());
+                            (None, 1)
+                        }
+                    };
+
+                    let num_qubits = num_qubits.filter(|num_qubits| {
+                        let valid = (1..=8).contains(num_qubits);
+                        if !valid {
+                            self.report(Severity::Error, args[1], format!("Registers hold between 1 and 8 (inclusive) qubits, found {num_qubits}"));
+                        }
+                        valid
+                    });
+
+                    if let (Some(name), Some(num_qubits)) = (name, num_qubits) {
+                        let definition = self.define(args[0], name, "Register");
+                        let register = self.registers.allocate(num_qubits, RegisterInfo { definition, ancestors: Vec::new() });
+                        let end = register.qubits.iter().max().unwrap() + 1;
+                        if self.measured.len() < end {
+                            self.measured.resize(end, false);
+                        }
+                        self.set_measured(&register, false);
+                        scope.registers.insert(name.to_owned(), register);
+                    }
+                    arity
+                },
+                TokenType::Select => {
+                    let name = self.identifier(arg(0), first_token, "subregister name");
+                    let parent = self.register(arg(1), first_token, scope);
+                    let offset = self.number(arg(2), first_token, "offset");
+                    let num_qubits = self.number(arg(3), first_token, "number of qubits");
+
+                    if let (Some(name), Some(parent), Some(offset), Some(num_qubits)) = (name, parent, offset, num_qubits) {
+                        let len = parent.len();
+                        if offset >= len {
+                            self.report(Severity::Error, args[2], format!("Offset {offset} is outside of register {} (0..{len})", args[1].ty));
+                        } else if num_qubits == 0 || offset + num_qubits > len {
+                            self.report(Severity::Error, args[3], format!("Number of qubits must be between 1 and {}, found {num_qubits}", len - offset));
+                        } else {
+                            let definition = self.define(args[0], name, "Register");
+                            let mut ancestors = parent.info.ancestors.clone();
+                            ancestors.push(parent.info.definition);
+                            let register = parent.select(offset..offset + num_qubits, RegisterInfo { definition, ancestors });
+
+                            let mut overlapping: Vec<&String> = scope.registers.iter()
+                                .filter(|(other_name, other)| {
+                                    other.allocation == register.allocation && *other_name != name
+                                        && other.qubits.iter().any(|qubit| register.qubits.contains(qubit))
+                                        && !register.info.ancestors.contains(&other.info.definition) && !other.info.ancestors.contains(&definition)
+                                })
+                                .map(|(other_name, _)| other_name)
+                                .collect();
+                            overlapping.sort();
+                            let messages: Vec<String> = overlapping.into_iter()
+                                .map(|other_name| format!("SELECT of {name} overlaps register {other_name}, which it is not selected from"))
+                                .collect();
+                            for message in messages {
+                                self.report(Severity::Warning, args[0], message);
+                            }
+
+                            scope.registers.insert(name.to_owned(), register);
+                        }
+                    }
+                    4
+                },
+                TokenType::Apply => {
+                    let operator = self.operator(arg(0), first_token, scope);
+                    let register = self.register(arg(1), first_token, scope);
+
+                    if let (Some(operator), Some(register)) = (operator, register) {
+                        let needed = 1 << register.len();
+                        if let Some(dim) = operator.dim.filter(|&dim| dim != needed) {
+                            self.report(Severity::Error, args[0], format!("Operator {} is {dim}x{dim}, but register {} needs a {needed}x{needed} operator", args[0].ty, args[1].ty));
+                        }
+
+                        if self.is_measured(&register) {
+                            self.set_measured(&register, false);
+                            self.report(Severity::Warning, args[1], format!("Operator applied to qubits of {} which were already measured", args[1].ty));
+                        }
+                    }
+                    2
+                },
+                TokenType::Identifier(name) => {
+                    let (dim, arity) = match arg(0).map(|token| &token.ty) {
+                        Some(TokenType::Tensor) => {
+                            let a = self.operator(arg(1), first_token, scope);
+                            let b = self.operator(arg(2), first_token, scope);
+                            let dim = a.zip(b).and_then(|(a, b)| Some(a.dim? * b.dim?));
+                            (Some(dim), 3)
+                        },
+                        Some(TokenType::Concat) => {
+                            let a = self.operator(arg(1), first_token, scope).and_then(|a| a.dim);
+                            let b = self.operator(arg(2), first_token, scope).and_then(|b| b.dim);
+                            if let (Some(a), Some(b)) = (a, b) {
+                                if a != b {
+                                    self.report(Severity::Error, args[0], format!("CONCAT needs operators of the same size, found {a}x{a} and {b}x{b}"));
+                                }
+                            }
+                            (Some(a.or(b)), 3)
+                        },
+                        Some(TokenType::Inverse) => {
+                            let a = self.operator(arg(1), first_token, scope);
+                            (Some(a.and_then(|a| a.dim)), 2)
+                        },
+                        Some(ty) => {
+                            self.report(Severity::Error, args[0], format!("Expected an operator macro (TENSOR, CONCAT, INVERSE), found {ty}"));
+                            (None, 1)
+                        },
+                        None => {
+                            self.report(Severity::Error, first_token, format!("Missing operator macro (TENSOR, CONCAT, INVERSE) for {name}"));
+                            (None, 0)
+                        }
+                    };
+
+                    // Defined even if its arguments weren't, so uses further down aren't reported as well
+                    if let Some(dim) = dim {
+                        let definition = self.define(first_token, name, "Operator");
+                        scope.operators.insert(name.clone(), OperatorInfo { dim, definition: Some(definition) });
+                    }
+                    arity
+                },
+                TokenType::Measure | TokenType::Peek => {
+                    // MEASURE MEASURE R peeks like PEEK R, which doesn't collapse anything
+                    let skip = (matches!(first_token.ty, TokenType::Measure) && matches!(arg(0), Some(Token { ty: TokenType::Measure, ..}))) as usize;
+                    let peek = skip == 1 || matches!(first_token.ty, TokenType::Peek);
+                    if let Some(register) = self.register(arg(skip), first_token, scope).filter(|_| !peek) {
+                        self.set_measured(&register, true);
+                    }
+                    skip + 1
+                },
+                TokenType::Set => {
+                    let register = self.register(arg(0), first_token, scope);
+                    match arg(1) {
+                        Some(Token { ty: TokenType::ByteArray(bits), ..}) => {
+                            if let Some(register) = register.as_ref().filter(|register| register.len() != bits.len()) {
+                                self.report(Severity::Error, args[1], format!("Expected {} bits (the size of {}), found {}", register.len(), args[0].ty, bits.len()));
+                            }
+                        },
+                        Some(token) => { self.report(Severity::Error, token, format!("Expected basis state (BYTEARRAY), found {}", token.ty)); },
+                        None => { self.report(Severity::Error, first_token, "Missing basis state (BYTEARRAY) for SET".to_owned()); }
+                    }
+
+                    // Leaves a known basis state, so acting on it is fine
+                    if let Some(register) = register {
+                        self.set_measured(&register, false);
+                    }
+                    2
+                },
+                TokenType::Reset => {
+                    if let Some(register) = self.register(arg(0), first_token, scope) {
+                        self.set_measured(&register, false);
+                    }
+                    1
+                },
+                TokenType::Barrier => {
+                    for token in &args {
+                        self.register(Some(token), first_token, scope);
+                    }
+                    args.len()
+                },
+                TokenType::Discard => {
+                    if self.register(arg(0), first_token, scope).is_some() {
+                        self.discard(args[0], scope);
+                    }
+                    1
+                },
+                TokenType::Define => {
+                    let Some(end) = find_block_end(lines, line_index) else {
+                        self.report(Severity::Error, first_token, "Missing END for DEFINE".to_owned());
+                        return;
+                    };
+
+                    match arg(0) {
+                        Some(token @ Token { ty: TokenType::Call(name, params), ..}) => {
+                            if let Some(param) = params.iter().enumerate().find(|(i, param)| params[..*i].contains(param)).map(|(_, param)| param) {
+                                self.report(Severity::Error, token, format!("Parameter {param} is declared twice"));
+                            }
+
+                            let definition = self.define(token, name, "Subroutine");
+                            let body = lines[(line_index + 1)..end].iter().map(|line| line.to_vec()).collect();
+                            scope.subroutines.insert(name.clone(), Rc::new(SubroutineInfo { params: params.clone(), body, definition }));
+                        },
+                        Some(token) => { self.report(Severity::Error, token, format!("Expected subroutine signature NAME(PARAMS), found {}", token.ty)); },
+                        None => { self.report(Severity::Error, first_token, "Missing subroutine signature NAME(PARAMS) for DEFINE".to_owned()); }
+                    }
+                    next_line = end + 1;
+                    1
+                },
+                TokenType::Repeat => {
+                    let count = self.number(arg(0), first_token, "repetition count");
+                    let Some(end) = find_block_end(lines, line_index) else {
+                        self.report(Severity::Error, first_token, "Missing END for REPEAT".to_owned());
+                        return;
+                    };
+
+                    // A second pass catches problems carried from one repetition into the next
+                    for _ in 0..count.unwrap_or(1).clamp(1, 2) {
+                        let mut inner = scope.clone();
+                        self.registers.enter();
+                        self.check_lines(&lines[(line_index + 1)..end], &mut inner);
+                        self.registers.leave();
+                    }
+                    next_line = end + 1;
+                    1
+                },
+                TokenType::Call(name, call_args) => {
+                    self.call(first_token, name, call_args, scope);
+                    0
+                },
+                TokenType::End => {
+                    self.report(Severity::Error, first_token, "END without a matching DEFINE or REPEAT".to_owned());
+                    0
+                },
+                ty => {
+                    self.report(Severity::Error, first_token, format!("Expected an instruction, found {ty}"));
+                    0
+                }
+            };
+
+            if let Some(token) = arg(arity) {
+                self.report(Severity::Error, token, format!("Unexpected {} after the instruction", token.ty));
+            }
+
+            line_index = next_line;
+        }
+    }
+
+    // token names a register of the scope
+    fn discard(&mut self, token: &Token, scope: &mut Scope) {
+        let TokenType::Identifier(name) = &token.ty else { return };
+        match self.registers.discard(&mut scope.registers, name) {
+            Ok(_) => {},
+            Err(DiscardError::Visible) => {
+                self.report(Severity::Error, token, format!("Can only DISCARD registers whose state is not visible outside the current DEFINE / REPEAT body, not {}", token.ty));
+            },
+            Err(DiscardError::PartialOverlap(other_name)) => {
+                self.report(Severity::Error, token, format!("Register {other_name} partially overlaps {}, so it can't be DISCARDed", token.ty));
+            }
+        }
+    }
+
+    fn call(&mut self, first_token: &Token, name: &str, args: &[String], scope: &Scope) {
+        let Some(subroutine) = scope.subroutines.get(name).cloned() else {
+            self.report(Severity::Error, first_token, format!("Subroutine {name} does not exist at this point"));
+            return;
+        };
+        self.definitions[subroutine.definition].used = true;
+
+        if args.len() != subroutine.params.len() {
+            self.report(Severity::Error, first_token, format!("Subroutine {name} takes {} arguments, found {}", subroutine.params.len(), args.len()));
+            return;
+        }
+
+        // Without conditionals, recursion never ends
+        if self.call_stack.iter().any(|caller| caller == name) {
+            self.report(Severity::Error, first_token, format!("Subroutine {name} calls itself, which exceeds the maximum call depth"));
+            return;
+        }
+
+        let mut inner = Scope {
+            registers: HashMap::new(),
+            operators: scope.operators.clone(),
+            subroutines: scope.subroutines.clone()
+        };
+
+        for (param, arg) in subroutine.params.iter().zip(args) {
+            if let Some(register) = scope.registers.get(arg) {
+                self.definitions[register.info.definition].used = true;
+                inner.registers.insert(param.clone(), register.clone());
+            } else if let Some(operator) = scope.operators.get(arg) {
+                if let Some(definition) = operator.definition {
+                    self.definitions[definition].used = true;
+                }
+                inner.operators.insert(param.clone(), operator.clone());
+            } else {
+                self.report(Severity::Error, first_token, format!("Argument {arg} is neither a register nor an operator"));
+                return;
+            }
+        }
+
+        let body: Vec<&[Token]> = subroutine.body.iter().map(|line| line.as_slice()).collect();
+        self.call_stack.push(name.to_owned());
+        self.registers.enter();
+        self.check_lines(&body, &mut inner);
+        self.registers.leave();
+        self.call_stack.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emulator::lexer::scan;
+
+    use super::*;
+
+    fn summary(program: &str) -> Vec<(Severity, usize, usize, String)> {
+        let tokens = scan(&mut program.as_bytes()).unwrap();
+        check(&tokens).into_iter().map(|diagnostic| (diagnostic.severity, diagnostic.line(), diagnostic.col(), diagnostic.message)).collect()
+    }
+
+    #[test]
+    fn test_clean_program() {
+        let program = "
+        HI TENSOR I(2) H
+        HZ CONCAT H R(3.141592653589793)
+        X CONCAT HZ H
+        XX TENSOR X X
+        HH TENSOR H H
+        DEFINE ORACLE(Q)
+        APPLY HI Q
+        APPLY CNOT Q
+        APPLY HI Q
+        END
+        DEFINE DIFFUSE(Q)
+        APPLY HH Q
+        APPLY XX Q
+        ORACLE(Q)
+        APPLY XX Q
+        APPLY HH Q
+        END
+        INITIALIZE R 2
+        APPLY HH R
+        REPEAT 1
+        ORACLE(R)
+        DIFFUSE(R)
+        END
+        MEASURE R";
+
+        assert!(summary(program).is_empty());
+        assert!(summary("INITIALIZE R 1\nPEEK R\nMEASURE MEASURE R\nAPPLY H R\nMEASURE R").is_empty());
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let program = "INITIALIZE R 3
+SELECT A R 0 2
+SELECT B R 1 2
+SELECT C A 0 1
+HH TENSOR H H
+APPLY HH R
+APPLY Y A
+MEASURE C
+APPLY H C
+INITIALIZE UNUSED 1
+BAD CONCAT H CNOT
+MEASURE B
+SET B [01]
+APPLY HH B
+DISCARD Q
+MEASURE A EXTRA";
+
+        use Severity::*;
+        assert_eq!(summary(program), vec![
+            (Warning, 2, 7, "SELECT of B overlaps register A, which it is not selected from".to_owned()),
+            (Error, 5, 6, "Operator HH is 4x4, but register R needs a 8x8 operator".to_owned()),
+            (Error, 6, 6, "Operator Y does not exist at this point".to_owned()),
+            (Warning, 8, 8, "Operator applied to qubits of C which were already measured".to_owned()),
+            (Warning, 9, 11, "Register UNUSED is never used".to_owned()),
+            (Warning, 10, 0, "Operator BAD is never used".to_owned()),
+            (Error, 10, 4, "CONCAT needs operators of the same size, found 2x2 and 4x4".to_owned()),
+            (Error, 14, 8, "Register Q does not exist at this point".to_owned()),
+            (Error, 15, 10, "Unexpected EXTRA after the instruction".to_owned())
+        ]);
+    }
+
+    #[test]
+    fn test_blocks() {
+        let program = "DEFINE LOOP(Q)
+LOOP(Q)
+END
+DEFINE NEVER(Q)
+END
+INITIALIZE R 1
+REPEAT 3
+APPLY H R
+MEASURE R
+INITIALIZE T 1
+DISCARD R
+END
+LOOP(R,R)
+LOOP(R)
+FLIP(R)
+END";
+
+        use Severity::*;
+        assert_eq!(summary(program), vec![
+            (Error, 1, 0, "Subroutine LOOP calls itself, which exceeds the maximum call depth".to_owned()),
+            (Warning, 3, 7, "Subroutine NEVER is never used".to_owned()),
+            (Warning, 7, 8, "Operator applied to qubits of R which were already measured".to_owned()),
+            (Warning, 9, 11, "Register T is never used".to_owned()),
+            (Error, 10, 8, "Can only DISCARD registers whose state is not visible outside the current DEFINE / REPEAT body, not R".to_owned()),
+            (Error, 12, 0, "Subroutine LOOP takes 1 arguments, found 2".to_owned()),
+            (Error, 14, 0, "Subroutine FLIP does not exist at this point".to_owned()),
+            (Error, 15, 0, "END without a matching DEFINE or REPEAT".to_owned())
+        ]);
+    }
+
+    #[test]
+    fn test_discard() {
+        // Registers are laid out by the same table the emulator uses, so B still covers the last qubit after A is taken out of the middle
+        let program = "INITIALIZE R 4
+SELECT A R 1 2
+SELECT B R 3 1
+SELECT C R 0 1
+MEASURE B
+DISCARD A
+APPLY H B
+APPLY H C
+MEASURE R
+DISCARD R
+INITIALIZE S 2
+HH TENSOR H H
+APPLY HH S
+SELECT F S 0 1
+SELECT G S 0 2
+DISCARD F
+APPLY H G
+INITIALIZE Q 3
+SELECT X Q 0 2
+SELECT Y Q 1 2
+DISCARD X
+APPLY HH Y";
+
+        use Severity::*;
+        assert_eq!(summary(program), vec![
+            (Warning, 6, 8, "Operator applied to qubits of B which were already measured".to_owned()),
+            (Warning, 14, 7, "SELECT of G overlaps register F, which it is not selected from".to_owned()),
+            (Warning, 19, 7, "SELECT of Y overlaps register X, which it is not selected from".to_owned()),
+            (Error, 20, 8, "Register Y partially overlaps X, so it can't be DISCARDed".to_owned())
+        ]);
+    }
+}
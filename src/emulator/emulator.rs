@@ -1,57 +1,18 @@
-use core::num;
-use std::cell::Cell;
-use std::collections::HashMap;
-use std::hash::Hash;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::error::Error;
 
 use super::{
     lexer::{Token, TokenType, PrimitiveGate},
+    registers::{DiscardError, Register, RegisterMap, RegisterTable},
 };
 
+use crate::circuit::*;
 use crate::dynamic::*;
 
-struct Register {
-    pub state: Rc<Cell<Option<State>>>,
-    pub interval: (usize, usize)
-}
-
-impl Register {
-    fn new(state: Rc<Cell<Option<State>>>, interval: (usize, usize)) -> Self {
-        Self {
-            state,
-            interval
-        }
-    }
-    
-    fn apply(&self, gate: &Gate) -> Result<(),()> {
-        let range = self.interval.0..self.interval.1;
-        if gate.get().dim().0 == range.len() {
-            return Err(());
-        };
-
-        let mut x: State = self.state.take().unwrap();
-        x.apply_partial(range, gate);
-        self.state.set(Some(x));
-
-        Ok(())
-    }
-
-    fn measure(&self) -> Result<usize, ()> {
-        let interval = self.interval.0..self.interval.1;
-        let mut state = self.state.take().unwrap();
-        let measured = state.measure_partial_leave_state(interval);
-        self.state.set(Some(state));
-        Ok(measured)
-    }
-
-    fn len(&self) -> usize {
-        self.interval.1 - self.interval.0
-    }
-}
-
-fn parse_identifier<'a>(possible_token: Option<&'a Token>, first_token: &'a Token, label: &'static str) -> Result<String, RuntimeError<'a>> {
+fn parse_identifier(possible_token: Option<&Token>, first_token: &Token, label: &'static str) -> Result<String, RuntimeError> {
     match possible_token {
         Some(Token { ty: TokenType::Identifier(name), ..}) => {Ok(name.to_owned())},
         token if token.is_some() => { Err(RuntimeError::new(token, format!("Expected IDENTIFIER token for {label}, found"))) },
@@ -60,11 +21,8 @@ fn parse_identifier<'a>(possible_token: Option<&'a Token>, first_token: &'a Toke
     }
 }
 
-type RegisterMap<'a> = HashMap<String, Register>;
-
-
-fn get_register<'a,'r>(possible_token: Option<&'a Token>, first_token: &'a Token, registers: &'r RegisterMap) -> Result<&'r Register, RuntimeError<'a>> {
-    let register_name = parse_identifier(possible_token.clone(), first_token, "register name argument")?;
+fn get_register<'r>(possible_token: Option<&Token>, first_token: &Token, registers: &'r RegisterMap) -> Result<&'r Register, RuntimeError> {
+    let register_name = parse_identifier(possible_token, first_token, "register name argument")?;
     match registers.get(&register_name) {
         Some(entry) => Ok(entry),
         None => {
@@ -73,26 +31,72 @@ fn get_register<'a,'r>(possible_token: Option<&'a Token>, first_token: &'a Token
     }
 }
 
+// Operators are kept as expressions and only turned into gates on the qubits they are APPLYed to, so nothing gets multiplied out.
+enum Operator {
+    Gate(String, Rc<Gate>),
+    Identity(usize),
+    Tensor(Rc<Operator>, Rc<Operator>),
+    // Matrix product, the second operator acts first
+    Concat(Rc<Operator>, Rc<Operator>),
+    Inverse(Rc<Operator>)
+}
+
+impl Operator {
+    fn dim(&self) -> usize {
+        match self {
+            Operator::Gate(_, gate) => gate.dim(),
+            Operator::Identity(n) => *n,
+            Operator::Tensor(a, b) => a.dim() * b.dim(),
+            Operator::Concat(a, _) | Operator::Inverse(a) => a.dim()
+        }
+    }
+
+    // Appends the gates making up this operator (or its inverse) acting on qubits, in the order they are applied
+    fn lower(&self, qubits: &[usize], inverse: bool, gates: &mut Vec<Instruction>) {
+        match self {
+            Operator::Gate(name, gate) => {
+                let (name, gate) = match inverse {
+                    true => (format!("{name}†"), Rc::new(gate.inverse())),
+                    false => (name.clone(), gate.clone())
+                };
+                gates.push(Instruction::Gate { name, gate, qubits: qubits.to_vec() });
+            },
+            Operator::Identity(_) => {},
+            Operator::Tensor(a, b) => {
+                let split = a.dim().ilog2() as usize;
+                a.lower(&qubits[..split], inverse, gates);
+                b.lower(&qubits[split..], inverse, gates);
+            },
+            Operator::Concat(a, b) => {
+                let (first, second) = if inverse { (a, b) } else { (b, a) };
+                first.lower(qubits, inverse, gates);
+                second.lower(qubits, inverse, gates);
+            },
+            Operator::Inverse(a) => a.lower(qubits, !inverse, gates)
+        }
+    }
+}
 
-type OperatorMap = HashMap<String, Rc<Gate>>;
+type OperatorMap = HashMap<String, Rc<Operator>>;
 
-fn get_gate<'a,'o>(possible_token: Option<&'a Token>, first_token: &'a Token, gate_map: &'o OperatorMap) -> Result<Rc<Gate>, RuntimeError<'a>> {
+fn get_operator(possible_token: Option<&Token>, first_token: &Token, operator_map: &OperatorMap) -> Result<Rc<Operator>, RuntimeError> {
     match possible_token.as_ref() {
         Some(Token {ty: TokenType::Identifier(ident),..}) => {
-            match gate_map.get(ident) {
-                Some(gate_ref) => { Ok(gate_ref.clone()) },
+            match operator_map.get(ident) {
+                Some(operator_ref) => { Ok(operator_ref.clone()) },
                 None => {
                     Err(RuntimeError::new(possible_token, "Operator does not exist at this point in the program, for".to_owned()))
                 }
             }
         }, 
-        Some(Token {ty: TokenType::Gate(primitive), ..}) => {
-            Ok(match *primitive {
-                PrimitiveGate::CNOT => Rc::new(Gate::cnot()),
-                PrimitiveGate::H => Rc::new(Gate::hadamard()),
-                PrimitiveGate::R(theta) => Rc::new(Gate::phase_shift(theta)),
-                PrimitiveGate::I(n) => Rc::new(Gate::identity(n))
-            })
+        Some(Token {ty: ty @ TokenType::Gate(primitive), ..}) => {
+            let name = ty.to_string();
+            Ok(Rc::new(match *primitive {
+                PrimitiveGate::CNOT => Operator::Gate(name, Rc::new(Gate::cnot())),
+                PrimitiveGate::H => Operator::Gate(name, Rc::new(Gate::hadamard())),
+                PrimitiveGate::R(theta) => Operator::Gate(name, Rc::new(Gate::phase_shift(theta))),
+                PrimitiveGate::I(n) => Operator::Identity(n)
+            }))
         },
         Some(_) => {
             Err(RuntimeError::new(possible_token, "Expected operator identifier OR gate primitive, found".to_owned()))
@@ -103,45 +107,496 @@ fn get_gate<'a,'o>(possible_token: Option<&'a Token>, first_token: &'a Token, ga
     }
 }
 
+/// A user defined `DEFINE NAME(PARAMS) ... END` block, the body is stored line by line (each ending in a NewLine token).
+struct Subroutine {
+    params: Vec<String>,
+    body: Vec<Vec<Token>>
+}
+
+type SubroutineMap = HashMap<String, Rc<Subroutine>>;
+
+// Registers, operators and subroutines visible at some point of the program.
+// REPEAT bodies get a copy of the enclosing scope, subroutine bodies only see their parameters (plus operators and subroutines), names introduced inside either are dropped at END.
+#[derive(Clone, Default)]
+struct Scope {
+    registers: RegisterMap,
+    operators: OperatorMap,
+    subroutines: SubroutineMap
+}
+
+const MAX_CALL_DEPTH: usize = 64;
+
+// Turns instructions into circuit instructions, which are either recorded (when compiling) or run straight away (when emulating).
+#[derive(Default)]
+struct Compiler {
+    circuit: Circuit,
+    simulator: Option<Simulator>,
+    registers: RegisterTable
+}
+
+impl Compiler {
+    fn emulating() -> Self {
+        Self { simulator: Some(Simulator::new()), ..Self::default() }
+    }
+
+    // Outcome of a measurement or peek, if emulating
+    fn emit(&mut self, instruction: Instruction) -> Option<Outcome> {
+        match self.simulator.as_mut() {
+            Some(simulator) => simulator.run(&instruction),
+            None => {
+                self.circuit.push(instruction);
+                None
+            }
+        }
+    }
+
+    fn allocate(&mut self, bits: Vec<bool>) -> Register {
+        let register = self.registers.allocate(bits.len(), ());
+        self.emit(Instruction::Initialize { qubits: register.qubits.clone(), bits });
+        register
+    }
+
+    fn enter(&mut self) {
+        self.registers.enter();
+    }
+
+    // Measures out whatever was allocated in the body being left, the registers referring to it went out of scope
+    fn leave(&mut self) {
+        for qubits in self.registers.leave() {
+            self.emit(Instruction::Discard { qubits });
+        }
+    }
+
+    // Copy of the state register lives in, along with the qubits of it the register spans (None when compiling)
+    fn peek(&self, register: &Register) -> Option<(State, Range<usize>)> {
+        let (qubits, state) = self.simulator.as_ref()?.state_of(register.qubits[0])?;
+        // Registers only ever span a contiguous part of the state they live in
+        let start = qubits.iter().position(|&qubit| qubit == register.qubits[0])?;
+        Some((state.clone(), start..start + register.len()))
+    }
+}
+
+// Measures out the qubits of register `name`, see RegisterTable::discard
+fn discard(scope: &mut Scope, compiler: &mut Compiler, name: &str) -> Result<(), String> {
+    let qubits = compiler.registers.discard(&mut scope.registers, name).map_err(|error| match error {
+        DiscardError::Visible => format!("Can only DISCARD registers whose state is not visible outside the current DEFINE / REPEAT body, {name} for the"),
+        DiscardError::PartialOverlap(other_name) => format!("Register {other_name} partially overlaps {name}, for the")
+    })?;
+    compiler.emit(Instruction::Discard { qubits });
+    Ok(())
+}
+
+fn expect_new_line(possible_token: Option<&Token>, first_token: &Token) -> Result<(), RuntimeError> {
+    match possible_token {
+        Some(Token { ty: TokenType::NewLine, ..}) => Ok(()),
+        Some(token) => Err(RuntimeError::new(Some(token), "Expected new line token".to_owned())),
+        None => Err(RuntimeError::new(Some(first_token), "Missing new line token after the".to_owned()))
+    }
+}
+
+// Index of the END closing the DEFINE / REPEAT on line `start`.
+pub(super) fn find_block_end(lines: &[&[Token]], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        match line.first().map(|token| &token.ty) {
+            Some(TokenType::Define | TokenType::Repeat) => { depth += 1; },
+            Some(TokenType::End) => {
+                depth -= 1;
+                if depth == 0 { return Some(i); }
+            },
+            _ => {}
+        }
+    }
+    None
+}
+
+fn instruction_text(line: &[Token]) -> String {
+    line.iter()
+        .filter(|token| !matches!(token.ty, TokenType::NewLine))
+        .map(|token| token.ty.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn identifier_of(possible_token: Option<&Token>) -> Option<String> {
+    match possible_token {
+        Some(Token { ty: TokenType::Identifier(name), ..}) => Some(name.clone()),
+        _ => None
+    }
+}
+
+/// Record of one executed instruction. Structural lines (DEFINE, REPEAT, END, subroutine calls) are not recorded, the lines of their bodies are.
+#[derive(Clone, Debug)]
+pub struct TraceStep {
+    /// Line of the instruction, as in `Token::line`
+    pub line: usize,
+    pub instruction: String,
+    /// Register the instruction acted on (by its name in the scope it ran in)
+    pub register: Option<String>,
+    /// Qubits of `state` spanned by `register`
+    pub qubits: Option<Range<usize>>,
+    /// The state `register` lives in, after the instruction ran
+    pub state: Option<State>,
+    /// Result of a MEASURE
+    pub result: Option<usize>
+}
+
+// Quoted, with control characters escaped as \u00XX since JSON strings can't hold them raw
+fn json_string(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c)
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+// JSON has no NaN or infinities, those become null
+fn json_number(value: f64) -> String {
+    if value.is_finite() { value.to_string() } else { "null".to_owned() }
+}
+
+impl TraceStep {
+    pub fn to_json(&self) -> String {
+        let optional = |value: Option<String>| value.unwrap_or("null".to_owned());
+
+        let probabilities = match (&self.state, &self.qubits) {
+            (Some(state), Some(qubits)) => {
+                let probabilities: Vec<String> = state.probabilities_partial(qubits.clone()).iter().map(|&prob| json_number(prob)).collect();
+                format!("[{}]", probabilities.join(","))
+            },
+            _ => "null".to_owned()
+        };
+
+        let amplitudes = self.state.as_ref().map(|state| {
+            let amplitudes: Vec<String> = state.get().iter().map(|amp| format!("[{},{}]", json_number(amp.r), json_number(amp.i))).collect();
+            format!("[{}]", amplitudes.join(","))
+        });
+
+        format!("{{\"line\":{},\"instruction\":{},\"register\":{},\"qubits\":{},\"result\":{},\"probabilities\":{},\"amplitudes\":{}}}",
+            self.line,
+            json_string(&self.instruction),
+            optional(self.register.as_deref().map(json_string)),
+            optional(self.qubits.as_ref().map(|qubits| format!("[{},{}]", qubits.start, qubits.end))),
+            optional(self.result.map(|result| result.to_string())),
+            probabilities,
+            optional(amplitudes)
+        )
+    }
+}
+
+pub fn trace_to_json(trace: &[TraceStep]) -> String {
+    let steps: Vec<String> = trace.iter().map(|step| format!("  {}", step.to_json())).collect();
+    format!("[\n{}\n]", steps.join(",\n"))
+}
+
+/// Passed to the breakpoint handler before the instruction on `line` of `file` runs.
+pub struct Breakpoint {
+    /// The INCLUDEd file the instruction is in, as in `Token::file`, `None` for the program itself
+    pub file: Option<PathBuf>,
+    pub line: usize,
+    pub instruction: String,
+    /// Visible registers, the qubits they span and a copy of the state they live in
+    pub registers: Vec<(String, Range<usize>, State)>
+}
+
+pub enum DebugAction {
+    Continue,
+    /// Break again before the next instruction
+    Step,
+    Abort
+}
+
+type BreakpointHandler = Box<dyn FnMut(&Breakpoint) -> DebugAction>;
+
+#[derive(Default)]
+struct Debugger {
+    trace: Option<Vec<TraceStep>>,
+    breakpoints: HashSet<(Option<PathBuf>, usize)>,
+    handler: Option<BreakpointHandler>,
+    stepping: bool
+}
+
+impl Debugger {
+    fn pause(&mut self, line: &[Token], scope: &Scope, compiler: &Compiler) -> Result<(), RuntimeError> {
+        let first_token = &line[0];
+        let location = (first_token.file().map(Path::to_path_buf), first_token.line());
+        if !self.stepping && !self.breakpoints.contains(&location) {
+            return Ok(());
+        }
+        let Some(handler) = self.handler.as_mut() else {
+            return Ok(());
+        };
+
+        let mut registers: Vec<_> = scope.registers.iter().filter_map(|(name, register)| {
+            compiler.peek(register).map(|(state, qubits)| (name.clone(), qubits, state))
+        }).collect();
+        registers.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let breakpoint = Breakpoint {
+            file: location.0,
+            line: location.1,
+            instruction: instruction_text(line),
+            registers
+        };
+
+        match handler(&breakpoint) {
+            DebugAction::Continue => { self.stepping = false; },
+            DebugAction::Step => { self.stepping = true; },
+            DebugAction::Abort => {
+                return Err(RuntimeError::new(Some(first_token), "Execution aborted at breakpoint, at the".to_owned()));
+            }
+        }
+        Ok(())
+    }
+}
+
 //This is going to be cancer with const generic Vector / Matrix types.
 pub fn emulate(tokens: &Vec<Token>) -> Result<Vec<usize>, RuntimeError> {
-    let mut results = Vec::new();
+    Emulator::new().execute(tokens)
+}
+
+/// Like `emulate`, but also returns what was PEEKed at, in order with the measurement results.
+pub fn emulate_outcomes(tokens: &[Token]) -> Result<Vec<Outcome>, RuntimeError> {
+    Emulator::new().execute_outcomes(tokens)
+}
+
+pub(super) fn split_lines(tokens: &[Token]) -> Vec<&[Token]> {
+    tokens.split_inclusive(|token| matches!(token.ty, TokenType::NewLine)).collect()
+}
 
-    let mut states: Vec<Rc<Cell<Option<State>>>> = Vec::new();
-    let mut register_map: RegisterMap = HashMap::new();
-    let mut operators: OperatorMap = HashMap::new();
+/// Outcome counts of a multi-shot run, keyed by the MEASURE results of a shot (in program order).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Histogram {
+    counts: BTreeMap<Vec<usize>, usize>,
+    shots: usize
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, outcome: Vec<usize>) {
+        self.shots += 1;
+        self.counts.entry(outcome).and_modify(|count| *count += 1).or_insert(1);
+    }
+
+    pub fn shots(&self) -> usize {
+        self.shots
+    }
+
+    pub fn count(&self, outcome: &[usize]) -> usize {
+        self.counts.get(outcome).copied().unwrap_or(0)
+    }
+
+    // 0 when nothing has been recorded yet
+    pub fn frequency(&self, outcome: &[usize]) -> f64 {
+        if self.shots == 0 {
+            return 0.0;
+        }
+        self.count(outcome) as f64 / self.shots as f64
+    }
+
+    pub fn most_common(&self) -> Option<(&[usize], usize)> {
+        self.iter().max_by_key(|(_, count)| *count)
+    }
+
+    /// Outcomes in ascending order along with their counts.
+    pub fn iter(&self) -> impl Iterator<Item = (&[usize], usize)> {
+        self.counts.iter().map(|(outcome, count)| (outcome.as_slice(), *count))
+    }
+}
+
+/// Compiles the program into a circuit, naming the registers visible at its end.
+pub fn compile(tokens: &[Token]) -> Result<Circuit, RuntimeError> {
+    let mut scope = Scope::default();
+    let mut compiler = Compiler::default();
+    execute(&split_lines(tokens), &mut scope, &mut compiler, &mut Vec::new(), &mut Debugger::default(), 0)?;
+
+    let mut circuit = compiler.circuit;
+    for (name, register) in scope.registers {
+        circuit.name_register(name, register.qubits);
+    }
+    Ok(circuit)
+}
+
+/// Runs the program `shots` times. The program is compiled once, and everything up to its first measurement is only simulated once, later shots start from a copy of that state.
+pub fn emulate_shots(tokens: &[Token], shots: usize) -> Result<Histogram, RuntimeError> {
+    let circuit = compile(tokens)?;
+    let instructions = circuit.instructions();
+    let first_measurement = instructions.iter().position(|instruction| !instruction.is_deterministic()).unwrap_or(instructions.len());
+
+    let mut prefix = Simulator::new();
+    for instruction in &instructions[..first_measurement] {
+        prefix.run(instruction);
+    }
+
+    let mut histogram = Histogram::new();
+    for _ in 0..shots {
+        let mut simulator = prefix.clone();
+        let outcomes = instructions[first_measurement..].iter().filter_map(|instruction| simulator.run(instruction));
+        histogram.record(outcomes.filter_map(|outcome| outcome.measured()).collect());
+    }
+
+    Ok(histogram)
+}
+
+/// Runs the program recording a `TraceStep` for every executed instruction.
+pub fn emulate_traced(tokens: &[Token]) -> Result<(Vec<usize>, Vec<TraceStep>), RuntimeError> {
+    let mut emulator = Emulator::new();
+    emulator.enable_tracing();
+    let results = emulator.execute(tokens)?;
+    Ok((results, emulator.take_trace()))
+}
+
+/// Keeps registers, operators and subroutines alive between calls to `execute`, so a program can be fed in piece by piece (e.g. from a REPL).
+/// Instructions run on a `Simulator` as they are compiled.
+pub struct Emulator {
+    scope: Scope,
+    compiler: Compiler,
+    debugger: Debugger
+}
+
+impl Default for Emulator {
+    fn default() -> Self {
+        Self {
+            scope: Scope::default(),
+            compiler: Compiler::emulating(),
+            debugger: Debugger::default()
+        }
+    }
+}
+
+impl Emulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs the given lines, returning the results of the MEASUREs they performed.
+    pub fn execute(&mut self, tokens: &[Token]) -> Result<Vec<usize>, RuntimeError> {
+        let outcomes = self.execute_outcomes(tokens)?;
+        Ok(outcomes.iter().filter_map(Outcome::measured).collect())
+    }
+
+    /// Runs the given lines, returning the outcomes of the MEASUREs and PEEKs they performed in order.
+    pub fn execute_outcomes(&mut self, tokens: &[Token]) -> Result<Vec<Outcome>, RuntimeError> {
+        let mut outcomes = Vec::new();
+        execute(&split_lines(tokens), &mut self.scope, &mut self.compiler, &mut outcomes, &mut self.debugger, 0)?;
+        Ok(outcomes)
+    }
+
+    /// Start recording a `TraceStep` for every executed instruction.
+    pub fn enable_tracing(&mut self) {
+        self.debugger.trace.get_or_insert_with(Vec::new);
+    }
+
+    /// Steps recorded since tracing was enabled or the trace was last taken.
+    pub fn take_trace(&mut self) -> Vec<TraceStep> {
+        self.debugger.trace.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Files and lines (as in `Token::file` and `Token::line`) to call the breakpoint handler before, `None` for lines of the program itself.
+    pub fn set_breakpoints(&mut self, breakpoints: impl IntoIterator<Item = (Option<PathBuf>, usize)>) {
+        self.debugger.breakpoints = breakpoints.into_iter().collect();
+    }
+
+    pub fn on_breakpoint(&mut self, handler: impl FnMut(&Breakpoint) -> DebugAction + 'static) {
+        self.debugger.handler = Some(Box::new(handler));
+    }
+
+    /// Drops every register, operator and subroutine.
+    pub fn reset(&mut self) {
+        self.scope = Scope::default();
+        self.compiler = Compiler::emulating();
+    }
+
+    /// The full state register `name` lives in, along with the qubits of it the register spans.
+    pub fn register_state(&self, name: &str) -> Option<(State, Range<usize>)> {
+        self.scope.registers.get(name).and_then(|register| self.compiler.peek(register))
+    }
+
+    /// Probability of each basis state of register `name`, ignoring the rest of the state it lives in.
+    pub fn register_probabilities(&self, name: &str) -> Option<Vec<f64>> {
+        self.register_state(name).map(|(state, interval)| state.probabilities_partial(interval))
+    }
+
+    pub fn registers(&self) -> Vec<(&str, Range<usize>)> {
+        let mut registers: Vec<_> = self.scope.registers.iter().filter_map(|(name, register)| {
+            self.compiler.peek(register).map(|(_, qubits)| (name.as_str(), qubits))
+        }).collect();
+        registers.sort_by_key(|(name, _)| *name);
+        registers
+    }
+
+    /// Names and dimensions of the defined operators.
+    pub fn operators(&self) -> Vec<(&str, usize)> {
+        let mut operators: Vec<_> = self.scope.operators.iter().map(|(name, operator)| (name.as_str(), operator.dim())).collect();
+        operators.sort_by_key(|(name, _)| *name);
+        operators
+    }
+}
+
+fn execute(lines: &[&[Token]], scope: &mut Scope, compiler: &mut Compiler, results: &mut Vec<Outcome>, debugger: &mut Debugger, call_depth: usize) -> Result<(), RuntimeError> {
+    let mut line_index = 0;
+    while line_index < lines.len() {
+        let mut token_iter = lines[line_index].iter().peekable();
+        let mut next_line = line_index + 1;
 
-    let mut token_iter = tokens.iter().peekable();
-    while token_iter.peek().is_some() {
         let first_token = match token_iter.next() {
             Some(token) => token,
-            None => { continue; }
+            None => { line_index = next_line; continue; }
         };
 
+        debugger.pause(lines[line_index], scope, compiler)?;
+
+        // Filled in for the trace
+        let mut affected: Option<String> = None;
+        let mut measured: Option<usize> = None;
+        let mut traced = true;
+
         // dbg!(first_token);
         match &first_token.ty {
             TokenType::Initialize => {
-                let name = parse_identifier(token_iter.next(), &first_token, "register name argument")?;
+                let name = parse_identifier(token_iter.next(), first_token, "register name argument")?;
                 
+                // INITIALIZE R N, INITIALIZE R N [BITS] or INITIALIZE R [BITS]
                 let num_qubits_token = token_iter.next();
                 let num_qubits = match num_qubits_token.as_ref() {
                     Some(Token { ty: TokenType::Number(num_qubits), ..})  if (1..=8).contains(num_qubits) => {*num_qubits},
+                    Some(Token { ty: TokenType::ByteArray(bits), ..}) if (1..=8).contains(&bits.len()) => {bits.len()},
+                    Some(Token { ty: TokenType::ByteArray(_), ..}) => { return Err(RuntimeError::new(num_qubits_token, "Expected between 1-8 (inclusive) bits for the initial register state, found".to_owned())); },
                     Some(_) => { return Err(RuntimeError::new(num_qubits_token, "Expected NUMBER within 1-8 (inclusive) for register qubit num_qubits, found".to_owned())); },
                     None => { return Err(RuntimeError::new(Some(first_token), "Missing register num_qubits argument (NUMBER) for the".to_owned())); }
                 };
 
-                let state = State::from_qubits((0..num_qubits).map(|_| false));
-                let state_ref = Rc::new(Cell::new(Some(state)));
-                states.push(state_ref.clone());
-                register_map.insert(name, Register::new(state_ref, (0, num_qubits)));
+                let bits_token = match num_qubits_token {
+                    Some(Token { ty: TokenType::ByteArray(_), ..}) => num_qubits_token,
+                    _ => token_iter.next_if(|token| matches!(token.ty, TokenType::ByteArray(_)))
+                };
+                let bits = match bits_token {
+                    Some(Token { ty: TokenType::ByteArray(bits), ..}) if bits.len() == num_qubits => bits.clone(),
+                    Some(_) => { return Err(RuntimeError::new(bits_token, format!("Expected {num_qubits} bits for the initial register state, found"))); },
+                    None => vec![false; num_qubits]
+                };
+
+                let register = compiler.allocate(bits);
+                scope.registers.insert(name.clone(), register);
+                affected = Some(name);
             },
 
             TokenType::Select => {
                 let name_token = token_iter.next();
                 let name = parse_identifier(name_token, first_token, "subregister name")?;
 
-                let sub_register = get_register(token_iter.next(), &first_token, &register_map)?;
-                let len = sub_register.interval.1 - sub_register.interval.0;
+                let sub_register = get_register(token_iter.next(), first_token, &scope.registers)?;
+                let len = sub_register.len();
 
                 let offset_token = token_iter.next();
                 let sub_offset = match offset_token.as_ref() {
@@ -152,7 +607,7 @@ pub fn emulate(tokens: &Vec<Token>) -> Result<Vec<usize>, RuntimeError> {
                             return Err(RuntimeError::new(offset_token, format!("Offset outside of (sub)register bounds (0..{})", sub_register.len())));
                         }
                     },
-                    Some(token) => { return Err(RuntimeError::new(offset_token, "Expected offset argument (NUMBER), found".to_owned())); },
+                    Some(_) => { return Err(RuntimeError::new(offset_token, "Expected offset argument (NUMBER), found".to_owned())); },
                     None => { return Err(RuntimeError::new(Some(first_token), "Missing offset argument (NUMBER) for the".to_owned())); }
                 };
 
@@ -165,97 +620,247 @@ pub fn emulate(tokens: &Vec<Token>) -> Result<Vec<usize>, RuntimeError> {
                             return Err(RuntimeError::new(num_qubits_token, format!("NUMQUBITS must be between 1 and {}, for ", sub_register.len() - sub_offset)));
                         }
                     },
-                    Some(token) => { return Err(RuntimeError::new(num_qubits_token, "Expected NUMQUBITS argument (NUMBER), found".to_owned())); },
+                    Some(_) => { return Err(RuntimeError::new(num_qubits_token, "Expected NUMQUBITS argument (NUMBER), found".to_owned())); },
                     None => { return Err(RuntimeError::new(Some(first_token), "Missing NUMQUBITS argument (NUMBER) for the".to_owned())); }
                 };
 
-                let interval = ((sub_register.interval.0 + sub_offset),(sub_register.interval.0 + sub_offset + num_qubits));
-                register_map.insert(name, Register::new(sub_register.state.clone(), interval));
+                let register = sub_register.select(sub_offset..(sub_offset + num_qubits), ());
+                scope.registers.insert(name.clone(), register);
+                affected = Some(name);
             },
             TokenType::Apply => {                
-                let gate = get_gate(token_iter.next(), &first_token, &operators)?;
-                let register = get_register(token_iter.next(), &first_token, &register_map)?;
+                let operator = get_operator(token_iter.next(), first_token, &scope.operators)?;
+                let register_token = token_iter.next();
+                let register = get_register(register_token, first_token, &scope.registers)?;
+                affected = identifier_of(register_token);
 
-                if register.apply(gate.as_ref()).is_err() {
+                if operator.dim() != 1 << register.len() {
                     return Err(RuntimeError::new(Some(first_token), "Provided gate and register dimensions do not match.".to_owned()));
                 }
+
+                let mut gates = Vec::new();
+                operator.lower(&register.qubits, false, &mut gates);
+                for gate in gates {
+                    compiler.emit(gate);
+                }
             },
             TokenType::Identifier(operator_ident) => {
                 //TENSOR MACRO
                 if let Some(token) = token_iter.next() {
                     let operator = match token.ty {
                         TokenType::Tensor => {
-                            let a = get_gate(token_iter.next(), &first_token, &operators)?;
-                            let b = get_gate(token_iter.next(), &first_token, &operators)?;
-                            a.tensor_product(b.as_ref())
+                            let a = get_operator(token_iter.next(), first_token, &scope.operators)?;
+                            let b = get_operator(token_iter.next(), first_token, &scope.operators)?;
+                            Operator::Tensor(a, b)
                         },
                         TokenType::Concat => {
-                            let a = get_gate(token_iter.next(), &first_token, &operators)?;
-                            let b = get_gate(token_iter.next(), &first_token, &operators)?;
+                            let a = get_operator(token_iter.next(), first_token, &scope.operators)?;
+                            let b = get_operator(token_iter.next(), first_token, &scope.operators)?;
 
-                            (a.as_ref() * b.as_ref()).map_err(|_| {
-                                RuntimeError{token: Some(token), info: "Matrix multiplication requires first argument's column count matches second's row count. For".to_owned()}
-                            })?
+                            if a.dim() != b.dim() {
+                                return Err(RuntimeError::new(Some(token), "Matrix multiplication requires first argument's column count matches second's row count. For".to_owned()));
+                            }
+                            Operator::Concat(a, b)
                         },
                         TokenType::Inverse => {
-                            let a = get_gate(token_iter.next(), &first_token, &operators)?;
-                            
-                            a.as_ref().clone().inverse()
+                            let a = get_operator(token_iter.next(), first_token, &scope.operators)?;
+                            Operator::Inverse(a)
                         },
                         _ => {
                             return Err(RuntimeError::new(Some(token), "Expected an operator macro (TENSOR, CONCAT, INVERSE), instead found".to_owned()));
                         },
                     };
 
-                    operators.insert(operator_ident.clone(), Rc::new(operator));
+                    scope.operators.insert(operator_ident.clone(), Rc::new(operator));
                 } else {
                     return Err(RuntimeError::new(Some(first_token), "Assumed operator macro decleration, found no defenition. For".to_owned()));
                 }
             },
-            TokenType::Measure => {
-                let cheat = match token_iter.peek() {
-                    Some(Token {ty: TokenType::Measure, ..}) => {
-                        token_iter.next();
-                        true
+            TokenType::Measure | TokenType::Peek => {
+                // MEASURE MEASURE R is the same as PEEK R
+                let peek = matches!(first_token.ty, TokenType::Peek) || token_iter.next_if(|token| matches!(token.ty, TokenType::Measure)).is_some();
+
+                let register_token = token_iter.next();
+                let register = get_register(register_token, first_token, &scope.registers)?;
+                affected = identifier_of(register_token);
+
+                let qubits = register.qubits.clone();
+                let outcome = compiler.emit(if peek { Instruction::Peek { qubits } } else { Instruction::Measure { qubits } });
+                measured = outcome.as_ref().and_then(Outcome::measured);
+                results.extend(outcome);
+            },
+            TokenType::Set => {
+                let register_token = token_iter.next();
+                let register = get_register(register_token, first_token, &scope.registers)?;
+                affected = identifier_of(register_token);
+
+                let bits_token = token_iter.next();
+                match bits_token {
+                    Some(Token { ty: TokenType::ByteArray(bits), ..}) => {
+                        if bits.len() != register.len() {
+                            return Err(RuntimeError::new(bits_token, format!("Expected {} bits (the register size), found", register.len())));
+                        }
+                        compiler.emit(Instruction::Set { qubits: register.qubits.clone(), bits: bits.clone() });
                     },
-                    _ => false
+                    Some(_) => { return Err(RuntimeError::new(bits_token, "Expected basis state argument (BYTEARRAY), found".to_owned())); },
+                    None => { return Err(RuntimeError::new(Some(first_token), "Missing basis state argument (BYTEARRAY) for the".to_owned())); }
+                }
+            },
+            TokenType::Reset => {
+                let register_token = token_iter.next();
+                let register = get_register(register_token, first_token, &scope.registers)?;
+                affected = identifier_of(register_token);
+                compiler.emit(Instruction::Set { qubits: register.qubits.clone(), bits: vec![false; register.len()] });
+            },
+            TokenType::Barrier => {
+                // Only an ordering hint for circuit export / optimisation, nothing to do when emulating.
+                let mut qubits = Vec::new();
+                while let Some(token) = token_iter.next_if(|token| !matches!(token.ty, TokenType::NewLine)) {
+                    let register = get_register(Some(token), first_token, &scope.registers)?;
+                    for &qubit in &register.qubits {
+                        if !qubits.contains(&qubit) {
+                            qubits.push(qubit);
+                        }
+                    }
+                }
+                compiler.emit(Instruction::Barrier { qubits });
+            },
+            TokenType::Discard => {
+                let register_token = token_iter.next();
+                get_register(register_token, first_token, &scope.registers)?;
+                let name = identifier_of(register_token).unwrap();
+                discard(scope, compiler, &name).map_err(|info| RuntimeError::new(Some(first_token), info))?;
+            },
+            TokenType::Define => {
+                let (name, params) = match token_iter.next() {
+                    Some(Token { ty: TokenType::Call(name, params), ..}) => (name.clone(), params.clone()),
+                    Some(token) => { return Err(RuntimeError::new(Some(token), "Expected subroutine signature NAME(PARAMS), found".to_owned())); },
+                    None => { return Err(RuntimeError::new(Some(first_token), "Missing subroutine signature NAME(PARAMS) for the".to_owned())); }
+                };
+
+                if let Some((i, param)) = params.iter().enumerate().find(|(i, param)| params[..*i].contains(param)) {
+                    return Err(RuntimeError::new(Some(first_token), format!("Parameter {param} (#{i}) is declared twice, for the")));
+                }
+
+                let end = find_block_end(lines, line_index).ok_or_else(|| {
+                    RuntimeError::new(Some(first_token), "Missing END for the".to_owned())
+                })?;
+
+                let body = lines[(line_index + 1)..end].iter().map(|line| line.to_vec()).collect();
+                scope.subroutines.insert(name, Rc::new(Subroutine { params, body }));
+                next_line = end + 1;
+                traced = false;
+            },
+            TokenType::Repeat => {
+                let count_token = token_iter.next();
+                let count = match count_token {
+                    Some(Token { ty: TokenType::Number(count), ..}) => *count,
+                    Some(_) => { return Err(RuntimeError::new(count_token, "Expected repetition count (NUMBER), found".to_owned())); },
+                    None => { return Err(RuntimeError::new(Some(first_token), "Missing repetition count (NUMBER) for the".to_owned())); }
+                };
+
+                let end = find_block_end(lines, line_index).ok_or_else(|| {
+                    RuntimeError::new(Some(first_token), "Missing END for the".to_owned())
+                })?;
+
+                for _ in 0..count {
+                    let mut inner = scope.clone();
+                    compiler.enter();
+                    let result = execute(&lines[(line_index + 1)..end], &mut inner, compiler, results, debugger, call_depth);
+                    compiler.leave();
+                    result?;
+                }
+                next_line = end + 1;
+                traced = false;
+            },
+            TokenType::Call(name, args) => {
+                let subroutine = match scope.subroutines.get(name) {
+                    Some(subroutine) => subroutine.clone(),
+                    None => { return Err(RuntimeError::new(Some(first_token), "Subroutine does not exist at this point in the program, for".to_owned())); }
+                };
+
+                if args.len() != subroutine.params.len() {
+                    return Err(RuntimeError::new(Some(first_token), format!("Expected {} arguments, found {} for", subroutine.params.len(), args.len())));
+                }
+
+                if call_depth >= MAX_CALL_DEPTH {
+                    return Err(RuntimeError::new(Some(first_token), format!("Exceeded maximum subroutine call depth ({MAX_CALL_DEPTH}) at")));
+                }
+
+                let mut inner = Scope {
+                    registers: HashMap::new(),
+                    operators: scope.operators.clone(),
+                    subroutines: scope.subroutines.clone()
                 };
 
-                let register = get_register(token_iter.next(), &first_token, &register_map)?;
-                //let results = register.measure(cheat);
-                results.push(match cheat {
-                    true => register.measure().unwrap(), // todo add cheat back
-                    false => register.measure().unwrap()
-                });
+                for (param, arg) in subroutine.params.iter().zip(args) {
+                    if let Some(register) = scope.registers.get(arg) {
+                        inner.registers.insert(param.clone(), register.clone());
+                    } else if let Some(operator) = scope.operators.get(arg) {
+                        inner.operators.insert(param.clone(), operator.clone());
+                    } else {
+                        return Err(RuntimeError::new(Some(first_token), format!("Argument {arg} is neither a register nor an operator, for")));
+                    }
+                }
+
+                let body: Vec<&[Token]> = subroutine.body.iter().map(|line| line.as_slice()).collect();
+                compiler.enter();
+                let result = execute(&body, &mut inner, compiler, results, debugger, call_depth + 1);
+                compiler.leave();
+                result?;
+                traced = false;
+            },
+            TokenType::End => {
+                return Err(RuntimeError::new(Some(first_token), "Found END without a matching DEFINE or REPEAT, at".to_owned()));
             },
             _ => {}
         }
 
-        let new_line = token_iter.next().unwrap();
-        if let TokenType::NewLine = new_line.ty {} else {
-            return Err(RuntimeError::new(Some(new_line), "Expected new line token".to_owned()));
+        expect_new_line(token_iter.next(), first_token)?;
+
+        if let Some(trace) = debugger.trace.as_mut().filter(|_| traced) {
+            let peeked = affected.as_ref().and_then(|name| scope.registers.get(name)).and_then(|register| compiler.peek(register));
+            let (state, qubits) = peeked.unzip();
+            trace.push(TraceStep {
+                line: first_token.line(),
+                instruction: instruction_text(lines[line_index]),
+                qubits,
+                state,
+                register: affected,
+                result: measured
+            });
         }
+
+        line_index = next_line;
     }
 
-    Ok(results)
+    Ok(())
 }
 
 #[derive(Debug)]
-pub struct RuntimeError<'a> {
-    token: Option<&'a Token>,
+pub struct RuntimeError {
+    token: Option<Token>,
     info: String
 }
 
-impl<'a> RuntimeError<'a> {
-    pub fn new(token: Option<&'a Token>, info: String) -> Self {
+impl RuntimeError {
+    pub fn new(token: Option<&Token>, info: String) -> Self {
         Self {
-            token,
+            token: token.cloned(),
             info
         }
     }
+
+    pub fn token(&self) -> Option<&Token> {
+        self.token.as_ref()
+    }
+
+    pub fn info(&self) -> &str {
+        &self.info
+    }
 }
 
-impl<'a> std::fmt::Display for RuntimeError<'a> {
+impl std::fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self.token.as_ref() {
             Some(token) => {
@@ -268,15 +873,17 @@ impl<'a> std::fmt::Display for RuntimeError<'a> {
     }
 }
 
-impl<'a> Error for RuntimeError<'a> {}
+impl Error for RuntimeError {}
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::cell::Cell;
+    use std::rc::Rc;
 
+    use crate::complex::*;
     use crate::emulator::lexer::*;
 
-    use super::emulate;
+    use super::*;
 
     #[test]
     pub fn test_basic() {
@@ -288,17 +895,13 @@ MEASURE R".as_bytes();
         
         let tokens = scan(&mut program).unwrap();
         
-        let mut results_map = HashMap::<usize,u32>::new();
+        let histogram = emulate_shots(&tokens, 1000).unwrap();
 
-        for _ in 0..1000 {
-            let res = emulate(&tokens).unwrap()[0];
-            results_map.entry(res).and_modify(|count| *count += 1).or_insert(1);
-        }
-
-        assert!(*results_map.get(&0).unwrap() > 200);
-        assert!(*results_map.get(&1).unwrap() > 200);
-        assert!(*results_map.get(&2).unwrap() > 200);
-        assert!(*results_map.get(&3).unwrap() > 200);
+        assert_eq!(histogram.shots(), 1000);
+        assert!(histogram.count(&[0]) > 200);
+        assert!(histogram.count(&[1]) > 200);
+        assert!(histogram.count(&[2]) > 200);
+        assert!(histogram.count(&[3]) > 200);
     }
 
     #[test]
@@ -319,4 +922,419 @@ MEASURE R".as_bytes();
             assert!(results == vec![0,0] || results == vec![1,3]);
         }
     }    
-}
\ No newline at end of file
+
+    #[test]
+    pub fn test_repeat() {
+        let mut program = "
+        INITIALIZE R 1
+        REPEAT 4
+        APPLY H R
+        END
+        MEASURE R".as_bytes();
+
+        let tokens = scan(&mut program).unwrap();
+
+        for _ in 0..100 {
+            assert_eq!(emulate(&tokens).unwrap(), vec![0]);
+        }
+    }
+
+    #[test]
+    pub fn test_grover_subroutines() {
+        let mut program = "
+        HI TENSOR I(2) H
+        HZ CONCAT H R(3.141592653589793)
+        X CONCAT HZ H
+        XX TENSOR X X
+        HH TENSOR H H
+        DEFINE ORACLE(Q)
+        APPLY HI Q
+        APPLY CNOT Q
+        APPLY HI Q
+        END
+        DEFINE DIFFUSE(Q)
+        APPLY HH Q
+        APPLY XX Q
+        ORACLE(Q)
+        APPLY XX Q
+        APPLY HH Q
+        END
+        INITIALIZE R 2
+        APPLY HH R
+        REPEAT 1
+        ORACLE(R)
+        DIFFUSE(R)
+        END
+        MEASURE R".as_bytes();
+
+        let tokens = scan(&mut program).unwrap();
+
+        for _ in 0..100 {
+            assert_eq!(emulate(&tokens).unwrap(), vec![3]);
+        }
+    }
+
+    #[test]
+    pub fn test_subroutine_scoping() {
+        let inner_register = "
+        DEFINE ALLOC()
+        INITIALIZE T 1
+        END
+        ALLOC()
+        MEASURE T".as_bytes();
+
+        let inner_operator = "
+        REPEAT 1
+        U TENSOR H H
+        END
+        INITIALIZE R 2
+        APPLY U R".as_bytes();
+
+        let outer_register = "
+        INITIALIZE R 1
+        DEFINE F()
+        APPLY H R
+        END
+        F()".as_bytes();
+
+        let wrong_arity = "
+        DEFINE F(A,B)
+        APPLY H A
+        END
+        INITIALIZE R 1
+        F(R)".as_bytes();
+
+        let recursion = "
+        DEFINE F()
+        F()
+        END
+        F()".as_bytes();
+
+        for mut program in [inner_register, inner_operator, outer_register, wrong_arity, recursion] {
+            let tokens = scan(&mut program).unwrap();
+            assert!(emulate(&tokens).is_err());
+        }
+    }
+
+    #[test]
+    pub fn test_emulator_session() {
+        let mut emulator = Emulator::new();
+
+        let mut line = "INITIALIZE R 2".as_bytes();
+        assert!(emulator.execute(&scan(&mut line).unwrap()).unwrap().is_empty());
+
+        let mut lines = "SELECT S R 1 1\nAPPLY H S".as_bytes();
+        emulator.execute(&scan(&mut lines).unwrap()).unwrap();
+
+        let probabilities = emulator.register_probabilities("R").unwrap();
+        assert!((probabilities[0] - 0.5).abs() < 1e-9 && (probabilities[1] - 0.5).abs() < 1e-9);
+        assert_eq!(emulator.register_probabilities("S").unwrap().len(), 2);
+        assert_eq!(emulator.register_state("S").unwrap().1, 1..2);
+
+        let mut line = "U TENSOR H H".as_bytes();
+        emulator.execute(&scan(&mut line).unwrap()).unwrap();
+        assert_eq!(emulator.operators(), vec![("U", 4)]);
+
+        emulator.reset();
+        assert!(emulator.registers().is_empty());
+        assert!(emulator.operators().is_empty());
+    }
+
+    #[test]
+    pub fn test_trace() {
+        let mut program = "INITIALIZE R 2
+        DEFINE F(Q)
+        APPLY H Q
+        END
+        SELECT S R 0 1
+        F(S)
+        MEASURE R".as_bytes();
+
+        let tokens = scan(&mut program).unwrap();
+        let (results, trace) = emulate_traced(&tokens).unwrap();
+
+        let lines: Vec<usize> = trace.iter().map(|step| step.line).collect();
+        assert_eq!(lines, vec![0, 4, 2, 6]);
+        assert_eq!(trace[1].instruction, "SELECT S R 0 1");
+        assert_eq!(trace[2].register.as_deref(), Some("Q"));
+        assert_eq!(trace[2].qubits, Some(0..1));
+        assert_eq!(trace[3].result, Some(results[0]));
+
+        let json = trace_to_json(&trace);
+        assert!(json.starts_with("[\n  {\"line\":0,\"instruction\":\"INITIALIZE R 2\",\"register\":\"R\",\"qubits\":[0,2],\"result\":null,\"probabilities\":[1,0,0,0]"));
+
+        // Control characters and non-finite numbers have no literal JSON form
+        let step = TraceStep { line: 3, instruction: "A\t\"B\"\\\n\u{1}".to_owned(), register: None, qubits: None, state: None, result: None };
+        assert_eq!(step.to_json(), "{\"line\":3,\"instruction\":\"A\\u0009\\\"B\\\"\\\\\\u000a\\u0001\",\"register\":null,\"qubits\":null,\"result\":null,\"probabilities\":null,\"amplitudes\":null}");
+        assert_eq!(json_number(f64::NAN), "null");
+        assert_eq!(json_number(f64::NEG_INFINITY), "null");
+        assert_eq!(json_number(-0.25), "-0.25");
+    }
+
+    #[test]
+    pub fn test_breakpoints() {
+        let mut program = "INITIALIZE R 1
+        APPLY H R
+        MEASURE R".as_bytes();
+        let tokens = scan(&mut program).unwrap();
+
+        let hits = Rc::new(Cell::new(0));
+        let mut emulator = Emulator::new();
+        emulator.set_breakpoints([(None, 1)]);
+        let counter = hits.clone();
+        emulator.on_breakpoint(move |breakpoint| {
+            counter.set(counter.get() + 1);
+            assert_eq!(breakpoint.registers[0].0, "R");
+            if breakpoint.line == 1 { DebugAction::Step } else { DebugAction::Abort }
+        });
+
+        let err = emulator.execute(&tokens).unwrap_err();
+        assert_eq!(err.token().unwrap().line(), 2);
+        assert_eq!(hits.get(), 2);
+    }
+
+    #[test]
+    pub fn test_breakpoints_in_includes() {
+        let dir = std::env::temp_dir().join(format!("qasm_breakpoint_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("prepare.qasm"), "INITIALIZE R 1\nAPPLY H R\n").unwrap();
+        std::fs::write(dir.join("main.qasm"), "INCLUDE \"prepare.qasm\"\nAPPLY H R\nMEASURE R\n").unwrap();
+        let tokens = scan_file(dir.join("main.qasm")).unwrap();
+        let included = tokens[0].file().unwrap().to_path_buf();
+
+        // Line 1 of both files is an APPLY, each breakpoint only stops in its own file
+        for file in [None, Some(included.clone())] {
+            let hits = Rc::new(Cell::new(0));
+            let mut emulator = Emulator::new();
+            emulator.set_breakpoints([(file.clone(), 1)]);
+            let (counter, expected) = (hits.clone(), file.clone());
+            emulator.on_breakpoint(move |breakpoint| {
+                counter.set(counter.get() + 1);
+                assert_eq!((&breakpoint.file, breakpoint.line), (&expected, 1));
+                DebugAction::Continue
+            });
+            emulator.execute(&tokens).unwrap();
+            assert_eq!(hits.get(), 1, "{file:?}");
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    pub fn test_shots() {
+        let mut terminal = "
+        INITIALIZE R 2
+        U TENSOR H I(2)
+        APPLY U R
+        APPLY CNOT R
+        SELECT S R 0 1
+        MEASURE S
+        MEASURE R".as_bytes();
+
+        let mut mid_circuit = "
+        INITIALIZE R 1
+        APPLY H R
+        MEASURE R
+        APPLY H R
+        MEASURE R".as_bytes();
+
+        let terminal = scan(&mut terminal).unwrap();
+        assert_eq!(compile(&terminal).unwrap().to_string(), "qubits 2\nregister R q0 q1\nregister S q0\ninit [00] q0 q1\nH q0\nCNOT q0 q1\nmeasure q0\nmeasure q0 q1\n");
+
+        let histogram = emulate_shots(&terminal, 500).unwrap();
+        assert_eq!(histogram.count(&[0, 0]) + histogram.count(&[1, 3]), 500);
+        assert!(histogram.count(&[0, 0]) > 150 && histogram.count(&[1, 3]) > 150);
+
+        let mid_circuit = scan(&mut mid_circuit).unwrap();
+        assert_eq!(compile(&mid_circuit).unwrap().to_string(), "qubits 1\nregister R q0\ninit [0] q0\nH q0\nmeasure q0\nH q0\nmeasure q0\n");
+
+        let histogram = emulate_shots(&mid_circuit, 800).unwrap();
+        assert_eq!(histogram.iter().count(), 4);
+        assert!(histogram.iter().all(|(_, count)| count > 100));
+        assert!((histogram.frequency(histogram.most_common().unwrap().0) - 0.25).abs() < 0.1);
+
+        let empty = Histogram::new();
+        assert_eq!(empty.frequency(&[0]), 0.0);
+        assert_eq!(empty.most_common(), None);
+    }
+
+    #[test]
+    pub fn test_reset_barrier() {
+        let mut program = "
+        INITIALIZE R 2
+        U TENSOR H I(2)
+        APPLY U R
+        APPLY CNOT R
+        SELECT S R 0 1
+        BARRIER R S
+        RESET S
+        BARRIER
+        MEASURE S
+        MEASURE R".as_bytes();
+
+        let tokens = scan(&mut program).unwrap();
+        let histogram = emulate_shots(&tokens, 200).unwrap();
+        assert_eq!(histogram.count(&[0, 0]) + histogram.count(&[0, 1]), 200);
+        assert!(histogram.count(&[0, 1]) > 50);
+
+        let mut undefined = "BARRIER R".as_bytes();
+        assert!(emulate(&scan(&mut undefined).unwrap()).is_err());
+    }
+
+    #[test]
+    pub fn test_discard() {
+        let mut program = "
+        HZ CONCAT H R(3.141592653589793)
+        X CONCAT HZ H
+        INITIALIZE R 3
+        SELECT A R 0 2
+        SELECT B R 1 1
+        SELECT C R 2 1
+        APPLY X C
+        APPLY H B
+        DISCARD B
+        MEASURE C
+        MEASURE A
+        MEASURE R".as_bytes();
+
+        let tokens = scan(&mut program).unwrap();
+        for _ in 0..20 {
+            let mut emulator = Emulator::new();
+            assert_eq!(emulator.execute(&tokens).unwrap(), vec![1, 0, 1]);
+            assert_eq!(emulator.registers(), vec![("A", 0..1), ("C", 1..2), ("R", 0..2)]);
+        }
+
+        let use_after_discard = "
+        INITIALIZE R 2
+        SELECT B R 1 1
+        DISCARD B
+        APPLY H B".as_bytes();
+
+        let partial_overlap = "
+        INITIALIZE R 3
+        SELECT P R 0 2
+        SELECT Q R 1 2
+        DISCARD P".as_bytes();
+
+        let outer_scope = "
+        INITIALIZE R 2
+        REPEAT 1
+        DISCARD R
+        END".as_bytes();
+
+        let ancilla = "
+        INITIALIZE R 1
+        REPEAT 3
+        INITIALIZE A 1
+        APPLY H A
+        DISCARD A
+        END
+        MEASURE R".as_bytes();
+
+        for mut program in [use_after_discard, partial_overlap, outer_scope] {
+            assert!(emulate(&scan(&mut program).unwrap()).is_err());
+        }
+        let mut ancilla = ancilla;
+        assert_eq!(emulate(&scan(&mut ancilla).unwrap()).unwrap(), vec![0]);
+    }
+
+    #[test]
+    pub fn test_basis_state_initialization() {
+        let mut program = "
+        INITIALIZE R [0101]
+        INITIALIZE T 3 [110]
+        SELECT S T 1 2
+        MEASURE R
+        MEASURE T
+        U TENSOR H I(2)
+        APPLY U S
+        SET S [01]
+        MEASURE T".as_bytes();
+
+        let tokens = scan(&mut program).unwrap();
+        for _ in 0..20 {
+            assert_eq!(emulate(&tokens).unwrap(), vec![5, 6, 5]);
+        }
+
+        for mut program in ["INITIALIZE R 3 [01]".as_bytes(), "INITIALIZE R [000000000]".as_bytes(), "INITIALIZE R 2\nSET R [1]".as_bytes(), "INITIALIZE R 2\nSET R 3".as_bytes()] {
+            assert!(emulate(&scan(&mut program).unwrap()).is_err());
+        }
+    }
+
+    #[test]
+    pub fn test_compile() {
+        let mut program = "
+        HZ CONCAT H R(3.141592653589793)
+        X CONCAT HZ H
+        Y INVERSE X
+        U TENSOR Y CNOT
+        INITIALIZE R 3
+        APPLY U R
+        REPEAT 2
+        INITIALIZE A 1
+        APPLY H A
+        END
+        MEASURE R".as_bytes();
+
+        let tokens = scan(&mut program).unwrap();
+        let circuit = compile(&tokens).unwrap();
+        assert_eq!(circuit.to_string(), "qubits 4
+register R q0 q1 q2
+init [000] q0 q1 q2
+H† q0
+R(3.141592653589793)† q0
+H† q0
+CNOT q1 q2
+init [0] q3
+H q3
+discard q3
+init [0] q3
+H q3
+discard q3
+measure q0 q1 q2
+");
+
+        for _ in 0..20 {
+            assert_eq!(circuit.run(), vec![Outcome::Measured(4)]);
+            assert_eq!(emulate(&tokens).unwrap(), vec![4]);
+        }
+    }
+
+    #[test]
+    pub fn test_peek() {
+        let mut program = "
+        INITIALIZE R 2
+        U TENSOR H I(2)
+        APPLY U R
+        APPLY CNOT R
+        PEEK R
+        MEASURE MEASURE R
+        SELECT S R 1 1
+        PEEK S
+        MEASURE R
+        PEEK R".as_bytes();
+
+        let tokens = scan(&mut program).unwrap();
+        let half = C64::new(0.5_f64.sqrt(), 0.0);
+        let bell = Outcome::Peeked { probabilities: vec![0.5, 0.0, 0.0, 0.5], amplitudes: Some(vec![half, C64::ZERO, C64::ZERO, half]) };
+
+        for _ in 0..20 {
+            let outcomes = emulate_outcomes(&tokens).unwrap();
+            assert_eq!(outcomes.len(), 5);
+            for peeked in &outcomes[..2] {
+                let Outcome::Peeked { probabilities, amplitudes } = peeked else { panic!("expected a peek") };
+                let Outcome::Peeked { probabilities: expected, amplitudes: Some(expected_amplitudes) } = &bell else { unreachable!() };
+                assert!(probabilities.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-9));
+                assert!(amplitudes.as_ref().unwrap().iter().zip(expected_amplitudes).all(|(a, b)| a.fuzzy_equals(*b)));
+            }
+            assert!(matches!(&outcomes[2], Outcome::Peeked { probabilities, amplitudes: None } if (probabilities[0] - 0.5).abs() < 1e-9));
+
+            let measured = outcomes[3].measured().unwrap();
+            assert!(measured == 0 || measured == 3);
+            assert!(matches!(&outcomes[4], Outcome::Peeked { probabilities, .. } if probabilities[measured] == 1.0));
+        }
+
+        let results = emulate(&tokens).unwrap();
+        assert!(results == vec![0] || results == vec![3]);
+    }
+}
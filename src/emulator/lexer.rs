@@ -1,10 +1,15 @@
-use std::{collections::HashMap, io::{BufReader, Bytes, Read}};
-use std::io::BufRead;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
 #[derive(Debug, Clone)]
 pub struct Token {
     pub ty: TokenType,
-    pos: (usize, usize) 
+    pos: (usize, usize),
+    file: Option<Rc<Path>>
 }
 
 impl Token {
@@ -15,12 +20,18 @@ impl Token {
     pub fn col(&self) -> usize {
         self.pos.1
     }
+
+    /// The INCLUDEd file this token came from, `None` for the scanned stream itself.
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
 }
 
 #[derive(Clone, Debug)]
 pub enum TokenType {
     Identifier(String),
     Number(usize),
+    String(String),
     ByteArray(Vec<bool>),
     Gate(PrimitiveGate),
     Initialize,
@@ -30,6 +41,15 @@ pub enum TokenType {
     Tensor,
     Inverse,
     Measure,
+    Peek,
+    Set,
+    Reset,
+    Barrier,
+    Discard,
+    Define,
+    Repeat,
+    End,
+    Call(String, Vec<String>),
     NewLine,
 }
 
@@ -41,29 +61,249 @@ pub enum PrimitiveGate {
     I(usize)
 }
 
+// Writes tokens back out the way they appear in source
+impl std::fmt::Display for TokenType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenType::Identifier(name) => f.write_str(name),
+            TokenType::Number(n) => write!(f, "{n}"),
+            TokenType::String(text) => write!(f, "{text:?}"),
+            TokenType::ByteArray(bits) => {
+                f.write_str("[")?;
+                for &bit in bits {
+                    f.write_str(if bit { "1" } else { "0" })?;
+                }
+                f.write_str("]")
+            },
+            TokenType::Gate(PrimitiveGate::H) => f.write_str("H"),
+            TokenType::Gate(PrimitiveGate::CNOT) => f.write_str("CNOT"),
+            TokenType::Gate(PrimitiveGate::R(theta)) => write!(f, "R({theta})"),
+            TokenType::Gate(PrimitiveGate::I(n)) => write!(f, "I({n})"),
+            TokenType::Initialize => f.write_str("INITIALIZE"),
+            TokenType::Select => f.write_str("SELECT"),
+            TokenType::Apply => f.write_str("APPLY"),
+            TokenType::Concat => f.write_str("CONCAT"),
+            TokenType::Tensor => f.write_str("TENSOR"),
+            TokenType::Inverse => f.write_str("INVERSE"),
+            TokenType::Measure => f.write_str("MEASURE"),
+            TokenType::Peek => f.write_str("PEEK"),
+            TokenType::Set => f.write_str("SET"),
+            TokenType::Reset => f.write_str("RESET"),
+            TokenType::Barrier => f.write_str("BARRIER"),
+            TokenType::Discard => f.write_str("DISCARD"),
+            TokenType::Define => f.write_str("DEFINE"),
+            TokenType::Repeat => f.write_str("REPEAT"),
+            TokenType::End => f.write_str("END"),
+            TokenType::Call(name, args) => write!(f, "{name}({})", args.join(",")),
+            TokenType::NewLine => f.write_str("\n")
+        }
+    }
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LexErrorKind {
+    Io(String),
+    InvalidByteArray,
+    InvalidAngle,
+    InvalidIdentitySize,
+    InvalidNumber,
+    MissingSubroutineName,
+    InvalidArgument,
+    UnclosedParenthesis,
+    UnterminatedString,
+    InvalidInclude,
+    IncludeCycle
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexError {
+    kind: LexErrorKind,
+    pos: (usize, usize),
+    text: String,
+    file: Option<PathBuf>
+}
+
+impl LexError {
+    fn new(kind: LexErrorKind, pos: (usize, usize), text: &str, file: Option<&Rc<Path>>) -> Self {
+        Self {
+            kind,
+            pos,
+            text: text.to_owned(),
+            file: file.map(|file| file.to_path_buf())
+        }
+    }
+
+    pub fn kind(&self) -> &LexErrorKind {
+        &self.kind
+    }
 
-pub fn scan(stream: &mut impl BufRead) -> Result<Vec<Token>, &'static str>{
-    let mut output: Vec<Token> = Vec::new();
+    pub fn line(&self) -> usize {
+        self.pos.0
+    }
 
+    pub fn col(&self) -> usize {
+        self.pos.1
+    }
+
+    /// The offending source text
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The INCLUDEd file the error is in, `None` for the scanned stream itself.
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+}
+
+impl std::fmt::Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let description = match &self.kind {
+            LexErrorKind::Io(err) => return write!(f, "Failed to read source ({err}), at line {}", self.line()),
+            LexErrorKind::InvalidByteArray => "Byte arrays may only contain 0s and 1s, found",
+            LexErrorKind::InvalidAngle => "Failed to parse the angle of",
+            LexErrorKind::InvalidIdentitySize => "Failed to parse the size of",
+            LexErrorKind::InvalidNumber => "Number too large,",
+            LexErrorKind::MissingSubroutineName => "Missing subroutine name before argument list",
+            LexErrorKind::InvalidArgument => "Arguments are single names separated by commas, found",
+            LexErrorKind::UnclosedParenthesis => "Missing closing parenthesis for",
+            LexErrorKind::UnterminatedString => "Missing closing quote for string",
+            LexErrorKind::InvalidInclude => "Expected INCLUDE \"FILE\", found",
+            LexErrorKind::IncludeCycle => "File includes itself,"
+        };
+        write!(f, "{description} {}, at {}:{}", self.text, self.line(), self.col())?;
+        if let Some(file) = self.file() {
+            write!(f, " in {}", file.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for LexError {}
+
+/// Scans a program, INCLUDE paths are resolved relative to the working directory.
+pub fn scan(stream: &mut impl BufRead) -> Result<Vec<Token>, LexError> {
+    let mut output = Vec::new();
+    scan_into(stream, None, &mut Vec::new(), &mut output)?;
+    Ok(output)
+}
+
+/// Scans the program in the file at `path`, INCLUDE paths are resolved relative to the including file.
+pub fn scan_file(path: impl AsRef<Path>) -> Result<Vec<Token>, LexError> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|err| LexError::new(LexErrorKind::Io(err.to_string()), (0, 0), "", None))?;
+
+    let mut output = Vec::new();
+    let mut include_stack = path.canonicalize().into_iter().collect();
+    scan_into(&mut BufReader::new(file), None, &mut include_stack, &mut output)?;
+    Ok(output)
+}
+
+// Splits a line into words (and string literals), along with their column, stopping at comments.
+// Parentheses run to their closing one, so calls and gates like F(A, B) are one word whatever the spacing inside.
+fn split_line<'a>(line: &'a str, line_number: usize, file: Option<&Rc<Path>>) -> Result<Vec<(usize, &'a str)>, LexError> {
+    let mut words = Vec::new();
+    let mut chars = line.char_indices().enumerate().peekable();
+    while let Some((col, (start, c))) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        if c == '"' {
+            let mut escaped = false;
+            let end = loop {
+                match chars.next() {
+                    Some((_, (i, '"'))) if !escaped => break i + 1,
+                    Some((_, (_, c))) => { escaped = c == '\\' && !escaped; },
+                    None => { return Err(LexError::new(LexErrorKind::UnterminatedString, (line_number, col), &line[start..], file)); }
+                }
+            };
+            words.push((col, &line[start..end]));
+            continue;
+        }
+
+        let mut end = start + c.len_utf8();
+        let mut comment = c == '#' || line[start..].starts_with("//");
+        if !comment {
+            let mut depth = (c == '(') as usize;
+            while let Some(&(_, (i, next))) = chars.peek() {
+                if next.is_whitespace() && depth == 0 { break; }
+                if next == '#' || line[i..].starts_with("//") {
+                    comment = true;
+                    break;
+                }
+                match next {
+                    '(' => depth += 1,
+                    ')' => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+                end = i + next.len_utf8();
+                chars.next();
+            }
+            if depth > 0 {
+                return Err(LexError::new(LexErrorKind::UnclosedParenthesis, (line_number, col), line[start..end].trim_end(), file));
+            }
+            words.push((col, &line[start..end]));
+        }
+
+        if comment {
+            break;
+        }
+    }
+    Ok(words)
+}
+
+fn unescape(literal: &str) -> String {
+    let mut text = String::new();
+    let mut chars = literal[1..(literal.len() - 1)].chars();
+    while let Some(c) = chars.next() {
+        text.push(if c == '\\' { chars.next().unwrap_or(c) } else { c });
+    }
+    text
+}
+
+fn scan_into(stream: &mut impl BufRead, file: Option<Rc<Path>>, include_stack: &mut Vec<PathBuf>, output: &mut Vec<Token>) -> Result<(), LexError> {
     let keywords = HashMap::from([("INITIALIZE", TokenType::Initialize), 
                                  ("SELECT", TokenType::Select),
                                  ("APPLY", TokenType::Apply),
                                  ("MEASURE", TokenType::Measure),
+                                 ("PEEK", TokenType::Peek),
                                  ("TENSOR", TokenType::Tensor),
                                  ("CONCAT", TokenType::Concat),
-                                 ("INVERSE", TokenType::Inverse)]);
-
-    
+                                 ("INVERSE", TokenType::Inverse),
+                                 ("SET", TokenType::Set),
+                                 ("RESET", TokenType::Reset),
+                                 ("BARRIER", TokenType::Barrier),
+                                 ("DISCARD", TokenType::Discard),
+                                 ("DEFINE", TokenType::Define),
+                                 ("REPEAT", TokenType::Repeat),
+                                 ("END", TokenType::End)]);
 
     for (line_number, line) in stream.lines().enumerate() {
-        let line = line.unwrap();
-        if line.len() == 0 {
+        let line = line.map_err(|err| LexError::new(LexErrorKind::Io(err.to_string()), (line_number, 0), "", file.as_ref()))?;
+        let words = split_line(&line, line_number, file.as_ref())?;
+
+        let Some(&(last_col, last_word)) = words.last() else {
+            continue;
+        };
+
+        if let Some(&(col, "INCLUDE")) = words.first() {
+            let path = match words.get(1) {
+                Some((_, literal)) if words.len() == 2 && literal.starts_with('"') => unescape(literal),
+                _ => { return Err(LexError::new(LexErrorKind::InvalidInclude, (line_number, col), line.trim(), file.as_ref())); }
+            };
+            include(&path, (line_number, col), file.as_ref(), include_stack, output)?;
             continue;
         }
-        let mut col = 0;
-        for word in line.split_whitespace() {
+
+        for (col, word) in words {
+            let error = |kind| LexError::new(kind, (line_number, col), word, file.as_ref());
+
             let token_type = if let Some(token_type) = keywords.get(word) {
                 token_type.clone()
+            } else if word.starts_with('"') {
+                TokenType::String(unescape(word))
             } else if let Some(byte_array) =  word.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
                 let mut bits = Vec::new();
                 for &b in byte_array.as_bytes() {
@@ -72,7 +312,7 @@ pub fn scan(stream: &mut impl BufRead) -> Result<Vec<Token>, &'static str>{
                     } else if b == b'1' {
                         bits.push(true);
                     } else {
-                        return Err("Test");
+                        return Err(error(LexErrorKind::InvalidByteArray));
                     }
                 }
 
@@ -87,42 +327,69 @@ pub fn scan(stream: &mut impl BufRead) -> Result<Vec<Token>, &'static str>{
                 )
             } else if let Some(theta) = word.strip_prefix("R(").and_then(|s| s.strip_suffix(")")) {
                 TokenType::Gate(
-                    PrimitiveGate::R(theta.parse().map_err(|_| "Failed to Parse Theta")?)
+                    PrimitiveGate::R(theta.trim().parse().map_err(|_| error(LexErrorKind::InvalidAngle))?)
                 )
             } else if let Some(n) = word.strip_prefix("I(").and_then(|s| s.strip_suffix(")")) {
                 TokenType::Gate(
-                    PrimitiveGate::I(n.parse().map_err(|_| "Failed to passe n")?)
+                    PrimitiveGate::I(n.trim().parse().map_err(|_| error(LexErrorKind::InvalidIdentitySize))?)
                 )
-            } else if word.len() >= 1 && word.chars().all(|c| c.is_numeric()) {
-                TokenType::Number(word.parse().unwrap())
-            } else if word.len() >= 1 {
-                TokenType::Identifier(word.to_owned())
+            } else if let Some((name, args)) = word.strip_suffix(')').and_then(|s| s.split_once('(')) {
+                if name.is_empty() {
+                    return Err(error(LexErrorKind::MissingSubroutineName));
+                }
+                let args: Vec<String> = args.split(',').map(str::trim).filter(|arg| !arg.is_empty()).map(|arg| arg.to_owned()).collect();
+                if args.iter().any(|arg| arg.contains(|c: char| c.is_whitespace() || c == '(' || c == ')')) {
+                    return Err(error(LexErrorKind::InvalidArgument));
+                }
+                TokenType::Call(name.to_owned(), args)
+            } else if word.chars().all(|c| c.is_ascii_digit()) {
+                TokenType::Number(word.parse().map_err(|_| error(LexErrorKind::InvalidNumber))?)
             } else {
-                panic!("Unexpected whitespace.");
+                TokenType::Identifier(word.to_owned())
             };
 
             output.push(Token {
                 ty: token_type,
-                pos: (line_number, col)
+                pos: (line_number, col),
+                file: file.clone()
             });
-
-            col += word.len() + 1;
         }
 
         output.push(Token {
             ty: TokenType::NewLine,
-            pos: (line_number, col - 1)
+            pos: (line_number, last_col + last_word.chars().count()),
+            file: file.clone()
         })
     }
 
-    Ok(output)
+    Ok(())
+}
+
+fn include(path: &str, pos: (usize, usize), file: Option<&Rc<Path>>, include_stack: &mut Vec<PathBuf>, output: &mut Vec<Token>) -> Result<(), LexError> {
+    // The innermost file being scanned is last on the stack
+    let resolved = match include_stack.last().and_then(|current| current.parent()) {
+        Some(dir) => dir.join(path),
+        None => PathBuf::from(path)
+    };
+    let io_error = |err: std::io::Error| LexError::new(LexErrorKind::Io(err.to_string()), pos, path, file);
+
+    let canonical = resolved.canonicalize().map_err(io_error)?;
+    if include_stack.contains(&canonical) {
+        return Err(LexError::new(LexErrorKind::IncludeCycle, pos, path, file));
+    }
+
+    let mut reader = BufReader::new(File::open(&resolved).map_err(io_error)?);
+    include_stack.push(canonical);
+    scan_into(&mut reader, Some(Rc::from(resolved.as_path())), include_stack, output)?;
+    include_stack.pop();
+    Ok(())
 }
 
 #[cfg(test)]
 pub mod tests {
-    use std::io::BufReader;
+    use std::fs;
 
-    use super::scan;
+    use super::*;
 
     #[test]
     fn debug_test() {
@@ -150,4 +417,92 @@ MEASURE R RES".as_bytes();
         let tokens = scan(&mut program).unwrap();
         dbg!(&tokens);
     }
+
+    #[test]
+    fn test_subroutine_tokens() {
+        let mut program = "DEFINE STEP(A,B)
+APPLY H A
+END
+STEP(R,S)
+NOP()
+STEP( R , S )   STEP(R,	S)".as_bytes();
+
+        let tokens = scan(&mut program).unwrap();
+        assert!(matches!(tokens[0].ty, TokenType::Define));
+        assert!(matches!(&tokens[1].ty, TokenType::Call(name, args) if name == "STEP" && args == &["A", "B"]));
+        assert!(matches!(tokens[7].ty, TokenType::End));
+        assert!(matches!(&tokens[9].ty, TokenType::Call(name, args) if name == "STEP" && args == &["R", "S"]));
+        assert!(matches!(&tokens[11].ty, TokenType::Call(name, args) if name == "NOP" && args.is_empty()));
+
+        // Spaces inside the parentheses don't split the call
+        assert!(matches!(&tokens[13].ty, TokenType::Call(name, args) if name == "STEP" && args == &["R", "S"]));
+        assert!(matches!(&tokens[14].ty, TokenType::Call(name, args) if name == "STEP" && args == &["R", "S"]));
+        assert_eq!((tokens[14].col(), tokens[15].col()), (16, 26));
+        assert!(matches!(tokens[15].ty, TokenType::NewLine));
+        assert!(matches!(&scan(&mut "U TENSOR R( 0.5 ) I( 2 )".as_bytes()).unwrap()[2].ty, TokenType::Gate(PrimitiveGate::R(theta)) if *theta == 0.5));
+    }
+
+    #[test]
+    fn test_comments_and_columns() {
+        let mut program = "# A comment line
+  INITIALIZE R 2 // trailing comment
+
+APPLY H#no space needed
+   
+MEASURE \"a b\\\" c\"".as_bytes();
+
+        let tokens = scan(&mut program).unwrap();
+        let summary: Vec<(String, usize, usize)> = tokens.iter().map(|token| (token.ty.to_string(), token.line(), token.col())).collect();
+        assert_eq!(summary, vec![
+            ("INITIALIZE".to_owned(), 1, 2), ("R".to_owned(), 1, 13), ("2".to_owned(), 1, 15), ("\n".to_owned(), 1, 16),
+            ("APPLY".to_owned(), 3, 0), ("H".to_owned(), 3, 6), ("\n".to_owned(), 3, 7),
+            ("MEASURE".to_owned(), 5, 0), ("\"a b\\\" c\"".to_owned(), 5, 8), ("\n".to_owned(), 5, 17)
+        ]);
+        assert!(matches!(&tokens[8].ty, TokenType::String(text) if text == "a b\" c"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases = [
+            ("SET R [01x1]", LexErrorKind::InvalidByteArray, (0, 6), "[01x1]"),
+            ("\nU TENSOR R(pi) H", LexErrorKind::InvalidAngle, (1, 9), "R(pi)"),
+            ("APPLY I(two) R", LexErrorKind::InvalidIdentitySize, (0, 6), "I(two)"),
+            ("REPEAT 99999999999999999999999", LexErrorKind::InvalidNumber, (0, 7), "99999999999999999999999"),
+            ("(A,B)", LexErrorKind::MissingSubroutineName, (0, 0), "(A,B)"),
+            ("STEP(A B)", LexErrorKind::InvalidArgument, (0, 0), "STEP(A B)"),
+            ("DEFINE STEP(A, B", LexErrorKind::UnclosedParenthesis, (0, 7), "STEP(A, B"),
+            ("STEP(A, # B)", LexErrorKind::UnclosedParenthesis, (0, 0), "STEP(A,"),
+            ("INCLUDE \"gates.qasm", LexErrorKind::UnterminatedString, (0, 8), "\"gates.qasm"),
+            ("INCLUDE gates.qasm", LexErrorKind::InvalidInclude, (0, 0), "INCLUDE gates.qasm")
+        ];
+
+        for (source, kind, pos, text) in cases {
+            let err = scan(&mut source.as_bytes()).unwrap_err();
+            assert_eq!((err.kind(), (err.line(), err.col()), err.text()), (&kind, pos, text));
+        }
+    }
+
+    #[test]
+    fn test_include() {
+        let dir = std::env::temp_dir().join(format!("qasm_include_test_{}", std::process::id()));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::write(dir.join("lib/gates.qasm"), "# shared gates\nHH TENSOR H H\n").unwrap();
+        fs::write(dir.join("main.qasm"), "INCLUDE \"lib/gates.qasm\"\nINITIALIZE R 2\nAPPLY HH R\n").unwrap();
+        fs::write(dir.join("a.qasm"), "INCLUDE \"b.qasm\"\n").unwrap();
+        fs::write(dir.join("b.qasm"), "INCLUDE \"a.qasm\"\n").unwrap();
+
+        let tokens = scan_file(dir.join("main.qasm")).unwrap();
+        assert!(matches!(&tokens[0].ty, TokenType::Identifier(name) if name == "HH"));
+        assert_eq!((tokens[0].line(), tokens[0].file()), (1, Some(dir.join("lib/gates.qasm").as_path())));
+        assert_eq!((tokens[5].line(), tokens[5].file()), (1, None));
+
+        let err = scan_file(dir.join("a.qasm")).unwrap_err();
+        assert_eq!(err.kind(), &LexErrorKind::IncludeCycle);
+        assert_eq!(err.file(), Some(dir.join("b.qasm").as_path()));
+
+        let err = scan(&mut "INCLUDE \"does/not/exist.qasm\"".as_bytes()).unwrap_err();
+        assert!(matches!(err.kind(), LexErrorKind::Io(_)));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
\ No newline at end of file
@@ -1,2 +1,4 @@
 pub mod lexer;
-pub mod emulator;
\ No newline at end of file
+pub mod emulator;
+pub mod checker;
+mod registers;
\ No newline at end of file
@@ -0,0 +1,131 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+
+// Register bookkeeping shared by the emulator and the checker, so both agree on which qubits a register covers
+
+// T is whatever else the user of the table keeps about a register
+#[derive(Clone)]
+pub(super) struct Register<T = ()> {
+    // Circuit qubits, the first one being the most significant
+    pub qubits: Vec<usize>,
+    // Key of the INITIALIZE these qubits came from in RegisterTable::allocations
+    pub allocation: usize,
+    pub info: T
+}
+
+impl<T> Register<T> {
+    pub fn len(&self) -> usize {
+        self.qubits.len()
+    }
+
+    // The qubits of a SELECT on this register
+    pub fn select<U>(&self, range: Range<usize>, info: U) -> Register<U> {
+        Register { qubits: self.qubits[range].to_vec(), allocation: self.allocation, info }
+    }
+}
+
+pub(super) type RegisterMap<T = ()> = HashMap<String, Register<T>>;
+
+// Why a DISCARD can't go ahead
+pub(super) enum DiscardError {
+    // The register's qubits are visible outside the current DEFINE / REPEAT body, whose registers couldn't be fixed up
+    Visible,
+    // Named register shares some but not all of the discarded qubits
+    PartialOverlap(String)
+}
+
+// The qubits brought in by one INITIALIZE
+struct Allocation {
+    // Qubits which were not DISCARDed yet
+    qubits: Vec<usize>,
+    // DEFINE / REPEAT body nesting it happened at
+    depth: usize
+}
+
+#[derive(Default)]
+pub(super) struct RegisterTable {
+    allocations: BTreeMap<usize, Allocation>,
+    next_allocation: usize,
+    // Qubits released by DISCARD or the end of a body, reused before new ones are taken
+    free: Vec<usize>,
+    next_qubit: usize,
+    depth: usize
+}
+
+impl RegisterTable {
+    pub fn allocate<T>(&mut self, num_qubits: usize, info: T) -> Register<T> {
+        self.free.sort_unstable();
+        let mut qubits: Vec<usize> = self.free.drain(..num_qubits.min(self.free.len())).collect();
+        while qubits.len() < num_qubits {
+            qubits.push(self.next_qubit);
+            self.next_qubit += 1;
+        }
+
+        self.allocations.insert(self.next_allocation, Allocation { qubits: qubits.clone(), depth: self.depth });
+        self.next_allocation += 1;
+        Register { qubits, allocation: self.next_allocation - 1, info }
+    }
+
+    pub fn enter(&mut self) {
+        self.depth += 1;
+    }
+
+    // Releases whatever was allocated in the body being left, the registers referring to it went out of scope.
+    // Returns the qubits of every such allocation which weren't DISCARDed already.
+    pub fn leave(&mut self) -> Vec<Vec<usize>> {
+        self.depth -= 1;
+        let ended: Vec<usize> = self.allocations.iter()
+            .filter(|(_, allocation)| allocation.depth > self.depth)
+            .map(|(&key, _)| key)
+            .collect();
+
+        let mut released = Vec::new();
+        for key in ended {
+            let allocation = self.allocations.remove(&key).unwrap();
+            if !allocation.qubits.is_empty() {
+                self.free.extend(&allocation.qubits);
+                released.push(allocation.qubits);
+            }
+        }
+        released
+    }
+
+    // Releases the qubits of register `name`, which are returned.
+    // Registers of the map inside it are dropped, the ones containing it lose its qubits.
+    pub fn discard<T>(&mut self, registers: &mut RegisterMap<T>, name: &str) -> Result<Vec<usize>, DiscardError> {
+        let (qubits, allocation) = (registers[name].qubits.clone(), registers[name].allocation);
+        if self.allocations[&allocation].depth < self.depth {
+            return Err(DiscardError::Visible);
+        }
+
+        let mut sharing: Vec<String> = registers.iter()
+            .filter(|(_, other)| other.allocation == allocation)
+            .map(|(other_name, _)| other_name.clone())
+            .collect();
+        sharing.sort();
+
+        for other_name in &sharing {
+            let other = &registers[other_name].qubits;
+            let overlaps = other.iter().any(|qubit| qubits.contains(qubit));
+            let contains = qubits.iter().all(|qubit| other.contains(qubit));
+            let inside = other.iter().all(|qubit| qubits.contains(qubit));
+            if overlaps && !contains && !inside {
+                return Err(DiscardError::PartialOverlap(other_name.clone()));
+            }
+        }
+
+        self.allocations.get_mut(&allocation).unwrap().qubits.retain(|qubit| !qubits.contains(qubit));
+        self.free.extend(&qubits);
+
+        for other_name in sharing {
+            let other = registers.get_mut(&other_name).unwrap();
+            if other.qubits.iter().all(|qubit| qubits.contains(qubit)) {
+                registers.remove(&other_name);
+            } else {
+                other.qubits.retain(|qubit| !qubits.contains(qubit));
+            }
+        }
+
+        Ok(qubits)
+    }
+}
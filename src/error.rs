@@ -0,0 +1,46 @@
+use std::fmt::Display;
+
+// Vectors are reported as (n, 1) shapes
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    DimensionMismatch { left: (usize, usize), right: (usize, usize) },
+    // Largest entry of U U† - I
+    NotUnitary { deviation: f64 },
+    // Largest entry of A - A†
+    NotHermitian { deviation: f64 },
+    NotNormalized { norm: f64 },
+    NotPowerOfTwo { dim: usize },
+    // First column elimination found no usable pivot in
+    Singular { column: usize },
+    IndexOutOfBounds { index: (usize, usize), dim: (usize, usize) },
+    // Counts rather than dimensions, which would overflow for large requests
+    TooManyQubits { qubits: usize, num_qubits: usize },
+    QubitOutOfRange { qubit: usize, num_qubits: usize },
+    // The same qubit given twice where distinct ones are needed
+    RepeatedQubit { qubit: usize },
+    // Fewer shots than an estimate needs
+    TooFewShots { shots: usize, needed: usize },
+    // A Trotter circuit of zero steps
+    TooFewSteps,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::DimensionMismatch { left: (m1, n1), right: (m2, n2) } => write!(f, "dimension mismatch, {m1}x{n1} against {m2}x{n2}"),
+            Error::NotUnitary { deviation } => write!(f, "matrix is not unitary, U U† is off the identity by {deviation}"),
+            Error::NotHermitian { deviation } => write!(f, "matrix is not hermitian, A is off A† by {deviation}"),
+            Error::NotNormalized { norm } => write!(f, "vector is not normalized, its norm is {norm}"),
+            Error::NotPowerOfTwo { dim } => write!(f, "dimension {dim} is not a power of two"),
+            Error::Singular { column } => write!(f, "matrix is singular, no pivot in column {column}"),
+            Error::IndexOutOfBounds { index: (r, c), dim: (m, n) } => write!(f, "index ({r}, {c}) is out of bounds for a {m}x{n} matrix"),
+            Error::TooManyQubits { qubits, num_qubits } => write!(f, "{qubits} qubits asked for from a {num_qubits} qubit state"),
+            Error::QubitOutOfRange { qubit, num_qubits } => write!(f, "qubit {qubit} is out of range for a {num_qubits} qubit state"),
+            Error::RepeatedQubit { qubit } => write!(f, "qubit {qubit} is given more than once"),
+            Error::TooFewShots { shots, needed } => write!(f, "{shots} shots is too few, at least {needed} are needed"),
+            Error::TooFewSteps => write!(f, "at least one Trotter step is needed"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
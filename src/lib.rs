@@ -13,4 +13,7 @@ pub mod static_;
 pub mod dynamic;
 
 pub mod misc;
-pub mod emulator;
\ No newline at end of file
+pub mod emulator;
+pub mod circuit;
+pub mod error;
+pub mod trotter;
\ No newline at end of file
@@ -0,0 +1,153 @@
+// Trotter-Suzuki circuits for the time evolution of Pauli sum Hamiltonians.
+
+use std::f64::consts::FRAC_PI_2;
+use std::rc::Rc;
+
+use crate::circuit::*;
+use crate::complex::*;
+use crate::dynamic::*;
+use crate::error::Error;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TrotterOrder {
+    // e^{-iHt} ≈ (Π_j e^{-i c_j P_j t/n})^n, error O(t²/n)
+    First,
+    // Symmetric product, half steps forwards then backwards through the terms, error O(t³/n²)
+    Second
+}
+
+fn gate(circuit: &mut Circuit, name: String, gate: Gate, qubits: Vec<usize>) {
+    circuit.push(Instruction::Gate { name, gate: Rc::new(gate), qubits });
+}
+
+// exp(-iθP) as a basis change into Z on every qubit of P, a CNOT ladder gathering their parity, RZ(2θ) on the last one, then the same in reverse.
+// The identity only contributes a global phase, so it emits nothing
+fn push_pauli_rotation(circuit: &mut Circuit, string: &PauliString, theta: f64) {
+    let support: Vec<(usize, Pauli)> = string.support().collect();
+    let Some(&(last, _)) = support.last() else { return };
+
+    for &(qubit, pauli) in &support {
+        match pauli {
+            Pauli::X => gate(circuit, "H".to_string(), Gate::hadamard(), vec![qubit]),
+            Pauli::Y => gate(circuit, format!("RX({FRAC_PI_2})"), Gate::rotation_x(FRAC_PI_2), vec![qubit]),
+            _ => {}
+        }
+    }
+    for pair in support.windows(2) {
+        gate(circuit, "CNOT".to_string(), Gate::cnot(), vec![pair[0].0, pair[1].0]);
+    }
+
+    gate(circuit, format!("RZ({})", 2.0 * theta), Gate::rotation_z(2.0 * theta), vec![last]);
+
+    for pair in support.windows(2).rev() {
+        gate(circuit, "CNOT".to_string(), Gate::cnot(), vec![pair[0].0, pair[1].0]);
+    }
+    for &(qubit, pauli) in &support {
+        match pauli {
+            Pauli::X => gate(circuit, "H".to_string(), Gate::hadamard(), vec![qubit]),
+            Pauli::Y => gate(circuit, format!("RX({})", -FRAC_PI_2), Gate::rotation_x(-FRAC_PI_2), vec![qubit]),
+            _ => {}
+        }
+    }
+}
+
+/// Approximates exp(-iHt) with the given (positive) number of Trotter steps, H has to be hermitian.
+pub fn trotter_circuit(hamiltonian: &PauliSum, t: f64, steps: usize, order: TrotterOrder) -> Result<Circuit, Error> {
+    if steps == 0 {
+        return Err(Error::TooFewSteps);
+    }
+    if !hamiltonian.is_hermitian() {
+        return Err(Error::NotHermitian { deviation: hamiltonian.hermitian_deviation() });
+    }
+
+    let mut circuit = Circuit::new();
+    let dt = t / steps as f64;
+    for _ in 0..steps {
+        match order {
+            TrotterOrder::First => for (coefficient, string) in hamiltonian.terms() {
+                push_pauli_rotation(&mut circuit, string, coefficient.r * dt);
+            },
+            TrotterOrder::Second => {
+                for (coefficient, string) in hamiltonian.terms() {
+                    push_pauli_rotation(&mut circuit, string, coefficient.r * dt / 2.0);
+                }
+                for (coefficient, string) in hamiltonian.terms().iter().rev() {
+                    push_pauli_rotation(&mut circuit, string, coefficient.r * dt / 2.0);
+                }
+            }
+        }
+    }
+    Ok(circuit)
+}
+
+// Distance between unitaries ignoring global phase, ‖A - e^{iφ} B‖_F / √dim with φ the best aligning phase.
+// Identity terms only shift the global phase, which is why it's left out
+fn distance(a: &Matrix<C64>, b: &Matrix<C64>) -> f64 {
+    let overlap = a.data.iter().zip(&b.data).fold(C64::ZERO, |acc, (x, y)| acc + y.conjugate() * *x);
+    let phase = C64::from_polar(1.0, overlap.arg());
+    let squared: f64 = a.data.iter().zip(&b.data).map(|(x, y)| (*x - *y * phase).modulus_squared()).sum();
+    (squared / a.dim().0 as f64).sqrt()
+}
+
+/// Error of the Trotter circuit against exact evolution by expm, for each of the given step counts.
+pub fn trotter_errors(hamiltonian: &PauliSum, t: f64, order: TrotterOrder, steps: impl IntoIterator<Item = usize>) -> Result<Vec<(usize, f64)>, Error> {
+    let num_qubits = hamiltonian.num_qubits();
+    let exact = hamiltonian.to_hamiltonian(num_qubits)?.evolution(t)?;
+
+    steps.into_iter().map(|steps| {
+        let mut circuit = trotter_circuit(hamiltonian, t, steps, order)?;
+        // Pins the size of the circuit even if the last qubit were only ever acted on by identities
+        circuit.push(Instruction::Barrier { qubits: (0..num_qubits).collect() });
+        let unitary = circuit.unitary().expect("Trotter circuits are made of gates only");
+        Ok((steps, distance(&unitary, exact.get())))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pauli_rotation() {
+        // A single term is simulated exactly by one step
+        for string in ["X0", "Y1", "Z0Z1", "X0Y1Z2", "Y0X2"] {
+            let hamiltonian: PauliSum = format!("0.7*{string}").parse().unwrap();
+            let errors = trotter_errors(&hamiltonian, 1.3, TrotterOrder::First, [1]).unwrap();
+            assert!(errors[0].1 < 1e-9, "{string}: {}", errors[0].1);
+        }
+
+        // Commuting terms too
+        let commuting: PauliSum = "0.5*Z0Z1 + 0.3*Z1 - 0.2*Z0Z2 + 1.5*I".parse().unwrap();
+        assert!(trotter_errors(&commuting, 2.0, TrotterOrder::First, [1]).unwrap()[0].1 < 1e-9);
+    }
+
+    #[test]
+    fn test_convergence() {
+        let ising: PauliSum = "0.5*Z0Z1 + 0.5*Z1Z2 + 0.3*X0 + 0.3*X1 + 0.3*X2".parse().unwrap();
+        let first = trotter_errors(&ising, 1.0, TrotterOrder::First, [4, 8, 16]).unwrap();
+        let second = trotter_errors(&ising, 1.0, TrotterOrder::Second, [4, 8, 16]).unwrap();
+
+        // Doubling the steps halves the first order error and quarters the second order one
+        for pair in first.windows(2) {
+            let ratio = pair[0].1 / pair[1].1;
+            assert!((1.7..2.3).contains(&ratio), "first order ratio {ratio}");
+        }
+        for pair in second.windows(2) {
+            let ratio = pair[0].1 / pair[1].1;
+            assert!((3.5..4.5).contains(&ratio), "second order ratio {ratio}");
+        }
+        assert!(second.iter().zip(&first).all(|((_, second), (_, first))| second < first));
+    }
+
+    #[test]
+    fn test_circuit() {
+        let circuit = trotter_circuit(&"0.25*X0Z1".parse().unwrap(), 1.0, 2, TrotterOrder::First).unwrap();
+        assert_eq!(circuit.to_string(), "qubits 2\nH q0\nCNOT q0 q1\nRZ(0.25) q1\nCNOT q0 q1\nH q0\nH q0\nCNOT q0 q1\nRZ(0.25) q1\nCNOT q0 q1\nH q0\n");
+
+        // Anti-hermitian parts that don't cancel can't be simulated
+        assert!(matches!(trotter_circuit(&"(0+0.5i)*X0".parse().unwrap(), 1.0, 1, TrotterOrder::First), Err(Error::NotHermitian { .. })));
+        assert!(trotter_circuit(&"(1+0.5i)*X0 + (1-0.5i)*X0".parse().unwrap(), 1.0, 1, TrotterOrder::First).is_ok());
+
+        assert_eq!(trotter_circuit(&"X0".parse().unwrap(), 1.0, 0, TrotterOrder::Second).err(), Some(Error::TooFewSteps));
+    }
+}